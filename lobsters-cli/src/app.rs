@@ -1,8 +1,8 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::Range;
 
 use crate::util;
-use lobsters::models::{ShortTag, Story, Tag};
+use lobsters::models::{Comment, Hat, Message, ShortTag, Story, StoryId, Tag, User};
 use lobsters::url::{self, Url};
 
 const STORY_HEIGHT: usize = 2;
@@ -13,8 +13,65 @@ pub struct State {
     current_story: usize,
     row_offset: usize,
     col_offset: usize,
+    upvoted: HashSet<StoryId>,
+    saved: HashSet<StoryId>,
+    hidden: HashSet<StoryId>,
+    /// What the stories are, shown for lists other than the one the app started with
+    title: Option<String>,
+    status: Option<String>,
+    unread_replies: Option<usize>,
 }
 
+/// The comments of a single story
+pub struct Thread {
+    story: Story,
+    comments: Vec<Comment>,
+    username: Option<String>,
+    /// The hats the logged in user can wear, loaded when they first comment
+    hats: Option<Vec<Hat>>,
+    current_comment: usize,
+    row_offset: usize,
+    status: Option<String>,
+}
+
+/// A user's profile and their recently submitted stories
+pub struct Profile {
+    user: User,
+    stories: Vec<Story>,
+    scroll: Scroll,
+}
+
+/// The private messages received by the user
+pub struct Inbox {
+    messages: Vec<Message>,
+    current_message: usize,
+    row_offset: usize,
+    status: Option<String>,
+}
+
+/// A private message being read, along with the rest of its thread
+pub struct Reader {
+    thread: Vec<Message>,
+    scroll: Scroll,
+}
+
+/// The position of a view that scrolls through its lines without a cursor
+#[derive(Default)]
+pub struct Scroll {
+    row_offset: usize,
+}
+
+/// The tags filtered out of the user's listings, being edited
+pub struct Filters {
+    tags: Vec<Tag>,
+    filtered: HashSet<String>,
+    current_tag: usize,
+    row_offset: usize,
+    status: Option<String>,
+    modified: bool,
+}
+
+#[derive(Clone)]
 pub struct TagMap {
     tags: HashMap<String, Tag>,
 }
@@ -30,9 +87,38 @@ impl State {
             current_story: 0,
             row_offset: 0,
             col_offset: 0,
+            upvoted: HashSet::new(),
+            saved: HashSet::new(),
+            hidden: HashSet::new(),
+            title: None,
+            status: None,
+            unread_replies: None,
         }
     }
 
+    /// Create a state for another list of stories, sharing the tags of this one
+    ///
+    /// The `title` describing the stories is shown alongside the status. Returns `None` if `stories` is empty.
+    pub fn with_stories(&self, stories: Vec<Story>, title: String) -> Option<Self> {
+        if stories.is_empty() {
+            return None;
+        }
+
+        Some(State {
+            stories,
+            tag_map: self.tag_map.clone(),
+            current_story: 0,
+            row_offset: 0,
+            col_offset: 0,
+            upvoted: HashSet::new(),
+            saved: self.saved.clone(),
+            hidden: self.hidden.clone(),
+            title: Some(title),
+            status: None,
+            unread_replies: self.unread_replies,
+        })
+    }
+
     pub fn stories(&self) -> &[Story] {
         &self.stories
     }
@@ -58,16 +144,105 @@ impl State {
     }
 
     pub fn story_url(&self) -> Result<Url, url::ParseError> {
-        match self.current_story().url.as_str() {
-            "" => self.comments_url(),
-            url => url.parse::<Url>(),
+        match &self.current_story().url {
+            Some(url) => Ok(url.clone()),
+            None => self.comments_url(),
         }
     }
 
+    /// The domain of the current story's URL without any `www.`, `None` for text stories
+    ///
+    /// The site lists stories by domain without the `www.`, as it does when displaying them.
+    pub fn story_domain(&self) -> Option<String> {
+        self.current_story()
+            .url
+            .as_ref()
+            .and_then(Url::domain)
+            .map(|domain| domain.trim_start_matches("www.").to_string())
+    }
+
     pub fn comments_url(&self) -> Result<Url, url::ParseError> {
         self.current_story().comments_url.parse::<Url>()
     }
 
+    /// Whether the current story has been upvoted during this session
+    ///
+    /// The stories don't say whether the user upvoted them before, so earlier votes aren't known.
+    pub fn is_upvoted(&self) -> bool {
+        self.upvoted.contains(&self.current_story().short_id)
+    }
+
+    /// Record a change to the vote on the current story
+    ///
+    /// The score is left alone, as it can't be known whether the vote changed it when the user
+    /// may have voted on the story before this session.
+    pub fn set_upvoted(&mut self, upvoted: bool) {
+        let story_id = self.current_story().short_id.clone();
+        if upvoted {
+            self.upvoted.insert(story_id);
+        } else {
+            self.upvoted.remove(&story_id);
+        }
+    }
+
+    pub fn is_saved(&self, story_id: &StoryId) -> bool {
+        self.saved.contains(story_id)
+    }
+
+    /// Record the stories that are known to be saved
+    pub fn set_saved_stories(&mut self, story_ids: HashSet<StoryId>) {
+        self.saved = story_ids;
+    }
+
+    /// Record a change to whether the current story is saved
+    pub fn set_saved(&mut self, saved: bool) {
+        let story_id = self.current_story().short_id.clone();
+        if saved {
+            self.saved.insert(story_id);
+        } else {
+            self.saved.remove(&story_id);
+        }
+    }
+
+    pub fn is_hidden(&self, story_id: &StoryId) -> bool {
+        self.hidden.contains(story_id)
+    }
+
+    /// Record a change to whether the current story is hidden
+    pub fn set_hidden(&mut self, hidden: bool) {
+        let story_id = self.current_story().short_id.clone();
+        if hidden {
+            self.hidden.insert(story_id);
+        } else {
+            self.hidden.remove(&story_id);
+        }
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
+    pub fn set_status<S: Into<String>>(&mut self, status: S) {
+        self.status = Some(status.into());
+    }
+
+    pub fn clear_status(&mut self) {
+        self.status = None;
+    }
+
+    /// The number of unread replies to the user, `None` if not known
+    pub fn unread_replies(&self) -> Option<usize> {
+        self.unread_replies
+    }
+
+    pub fn set_unread_replies(&mut self, count: Option<usize>) {
+        self.unread_replies = count;
+    }
+
     pub fn max_score_digits(&self) -> Option<usize> {
         self.stories
             .iter()
@@ -76,7 +251,11 @@ impl State {
     }
 
     pub fn get_tag(&self, tag: &ShortTag) -> Option<&Tag> {
-        self.tag_map.get(&tag)
+        self.tag_map.get(tag)
+    }
+
+    pub fn tag_map(&self) -> &TagMap {
+        &self.tag_map
     }
 
     pub fn row_offset_get_mut(&mut self) -> &mut usize {
@@ -121,6 +300,348 @@ impl State {
     }
 }
 
+impl Thread {
+    /// Create a thread for `story`, `username` is the logged in user, if any
+    pub fn new(story: Story, username: Option<String>) -> Self {
+        let mut thread = Thread {
+            story,
+            comments: Vec::new(),
+            username,
+            hats: None,
+            current_comment: 0,
+            row_offset: 0,
+            status: None,
+        };
+        thread.take_comments();
+        thread
+    }
+
+    pub fn story(&self) -> &Story {
+        &self.story
+    }
+
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    /// Replace the story with a freshly fetched copy, keeping the position in the thread
+    pub fn set_story(&mut self, story: Story) {
+        self.story = story;
+        self.take_comments();
+        self.current_comment = self
+            .current_comment
+            .min(self.comments.len().saturating_sub(1));
+    }
+
+    pub fn current_comment_index(&self) -> usize {
+        self.current_comment
+    }
+
+    pub fn current_comment(&self) -> Option<&Comment> {
+        self.comments.get(self.current_comment)
+    }
+
+    /// Whether the current comment was written by the logged in user
+    pub fn is_own_comment(&self) -> bool {
+        match (self.current_comment(), &self.username) {
+            (Some(comment), Some(username)) => comment.commenting_user.username == *username,
+            _ => false,
+        }
+    }
+
+    pub fn hats(&self) -> Option<&[Hat]> {
+        self.hats.as_deref()
+    }
+
+    pub fn set_hats(&mut self, hats: Vec<Hat>) {
+        self.hats = Some(hats);
+    }
+
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
+    pub fn set_status<S: Into<String>>(&mut self, status: S) {
+        self.status = Some(status.into());
+    }
+
+    pub fn clear_status(&mut self) {
+        self.status = None;
+    }
+
+    pub fn row_offset_get_mut(&mut self) -> &mut usize {
+        &mut self.row_offset
+    }
+
+    pub fn next_comment(&mut self) -> bool {
+        if self.current_comment + 1 < self.comments.len() {
+            self.current_comment += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn prev_comment(&mut self) -> bool {
+        if let Some(index) = self.current_comment.checked_sub(1) {
+            self.current_comment = index;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn take_comments(&mut self) {
+        self.comments = self.story.comments.take().unwrap_or_default();
+    }
+}
+
+impl Profile {
+    pub fn new(user: User, stories: Vec<Story>) -> Self {
+        Profile {
+            user,
+            stories,
+            scroll: Scroll::default(),
+        }
+    }
+
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
+    pub fn stories(&self) -> &[Story] {
+        &self.stories
+    }
+}
+
+impl AsMut<Scroll> for Profile {
+    fn as_mut(&mut self) -> &mut Scroll {
+        &mut self.scroll
+    }
+}
+
+impl Inbox {
+    pub fn new(messages: Vec<Message>) -> Self {
+        Inbox {
+            messages,
+            current_message: 0,
+            row_offset: 0,
+            status: None,
+        }
+    }
+
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    pub fn current_message_index(&self) -> usize {
+        self.current_message
+    }
+
+    pub fn current_message(&self) -> Option<&Message> {
+        self.messages.get(self.current_message)
+    }
+
+    /// Record that the current message has been read
+    pub fn mark_read(&mut self) {
+        if let Some(message) = self.messages.get_mut(self.current_message) {
+            message.has_been_read = true;
+        }
+    }
+
+    /// Remove the current message, after it has been deleted
+    pub fn remove_current(&mut self) {
+        if self.current_message < self.messages.len() {
+            self.messages.remove(self.current_message);
+            self.current_message = self
+                .current_message
+                .min(self.messages.len().saturating_sub(1));
+        }
+    }
+
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
+    pub fn set_status<S: Into<String>>(&mut self, status: S) {
+        self.status = Some(status.into());
+    }
+
+    pub fn clear_status(&mut self) {
+        self.status = None;
+    }
+
+    pub fn row_offset_get_mut(&mut self) -> &mut usize {
+        &mut self.row_offset
+    }
+
+    pub fn next_message(&mut self) -> bool {
+        if self.current_message + 1 < self.messages.len() {
+            self.current_message += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn prev_message(&mut self) -> bool {
+        if let Some(index) = self.current_message.checked_sub(1) {
+            self.current_message = index;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Reader {
+    pub fn new(thread: Vec<Message>) -> Self {
+        Reader {
+            thread,
+            scroll: Scroll::default(),
+        }
+    }
+
+    /// The messages of the thread, oldest first
+    pub fn thread(&self) -> &[Message] {
+        &self.thread
+    }
+}
+
+impl AsMut<Scroll> for Reader {
+    fn as_mut(&mut self) -> &mut Scroll {
+        &mut self.scroll
+    }
+}
+
+impl Scroll {
+    pub fn row_offset_get_mut(&mut self) -> &mut usize {
+        &mut self.row_offset
+    }
+
+    pub fn scroll_down(&mut self) {
+        self.row_offset += 1;
+    }
+
+    pub fn scroll_up(&mut self) -> bool {
+        if let Some(offset) = self.row_offset.checked_sub(1) {
+            self.row_offset = offset;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Filters {
+    /// Create an editor for the filters over all active tags, `filtered` are the tags that are
+    /// currently filtered
+    pub fn new(tag_map: &TagMap, filtered: Vec<ShortTag>) -> Self {
+        Filters {
+            tags: tag_map
+                .sorted_tags()
+                .into_iter()
+                .filter(|tag| tag.is_active())
+                .cloned()
+                .collect(),
+            filtered: filtered.into_iter().map(|tag| tag.0).collect(),
+            current_tag: 0,
+            row_offset: 0,
+            status: None,
+            modified: false,
+        }
+    }
+
+    pub fn tags(&self) -> &[Tag] {
+        &self.tags
+    }
+
+    pub fn is_filtered(&self, tag: &Tag) -> bool {
+        self.filtered.contains(&tag.tag)
+    }
+
+    /// Filter the current tag if it isn't already, otherwise stop filtering it
+    pub fn toggle_current(&mut self) {
+        if let Some(tag) = self.tags.get(self.current_tag) {
+            if !self.filtered.remove(&tag.tag) {
+                self.filtered.insert(tag.tag.clone());
+            }
+            self.modified = true;
+        }
+    }
+
+    /// The filtered tags, in the order they are listed
+    ///
+    /// Tags that were filtered but aren't listed, such as inactive tags, follow the listed ones
+    /// so that saving doesn't stop filtering them.
+    pub fn filtered_tags(&self) -> Vec<ShortTag> {
+        let listed: HashSet<&str> = self.tags.iter().map(|tag| tag.tag.as_str()).collect();
+        let mut unlisted: Vec<&str> = self
+            .filtered
+            .iter()
+            .map(String::as_str)
+            .filter(|tag| !listed.contains(tag))
+            .collect();
+        unlisted.sort();
+
+        self.tags
+            .iter()
+            .filter(|tag| self.is_filtered(tag))
+            .map(|tag| tag.tag.as_str())
+            .chain(unlisted)
+            .map(|tag| ShortTag(tag.to_string()))
+            .collect()
+    }
+
+    /// Whether there are changes that haven't been saved
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Record that the filters have been saved
+    pub fn set_saved(&mut self) {
+        self.modified = false;
+    }
+
+    pub fn current_tag_index(&self) -> usize {
+        self.current_tag
+    }
+
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
+    pub fn set_status<S: Into<String>>(&mut self, status: S) {
+        self.status = Some(status.into());
+    }
+
+    pub fn clear_status(&mut self) {
+        self.status = None;
+    }
+
+    pub fn row_offset_get_mut(&mut self) -> &mut usize {
+        &mut self.row_offset
+    }
+
+    pub fn next_tag(&mut self) -> bool {
+        if self.current_tag + 1 < self.tags.len() {
+            self.current_tag += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn prev_tag(&mut self) -> bool {
+        if let Some(index) = self.current_tag.checked_sub(1) {
+            self.current_tag = index;
+            true
+        } else {
+            false
+        }
+    }
+}
+
 impl TagMap {
     pub fn new(tags: Vec<Tag>) -> Self {
         let tags = tags.into_iter().fold(HashMap::new(), |mut map, tag| {
@@ -134,4 +655,51 @@ impl TagMap {
     pub fn get<'a>(&'a self, name: &ShortTag) -> Option<&'a Tag> {
         self.tags.get(&name.0)
     }
+
+    /// All of the tags, ordered by name
+    pub fn sorted_tags(&self) -> Vec<&Tag> {
+        let mut tags: Vec<_> = self.tags.values().collect();
+        tags.sort_by(|a, b| a.tag.cmp(&b.tag));
+        tags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(name: &str, active: bool) -> Tag {
+        let json = format!(
+            r#"{{"tag": "{}", "privileged": false, "is_media": false, "active": {}}}"#,
+            name, active
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    fn short_tags(tags: &[&str]) -> Vec<ShortTag> {
+        tags.iter().map(|tag| ShortTag(tag.to_string())).collect()
+    }
+
+    #[test]
+    fn filters_keep_unlisted_tags() {
+        let tag_map = TagMap::new(vec![
+            tag("go", true),
+            tag("nodejs", false),
+            tag("rust", true),
+        ]);
+        let mut filters = Filters::new(&tag_map, short_tags(&["rust", "nodejs", "removed"]));
+        assert_eq!(filters.tags().len(), 2);
+
+        // Stop filtering rust, the first listed tag is go
+        filters.next_tag();
+        filters.toggle_current();
+        assert_eq!(filters.filtered_tags(), short_tags(&["nodejs", "removed"]));
+
+        filters.prev_tag();
+        filters.toggle_current();
+        assert_eq!(
+            filters.filtered_tags(),
+            short_tags(&["go", "nodejs", "removed"])
+        );
+    }
 }
@@ -0,0 +1,77 @@
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::Error;
+
+/// A file in the temporary directory that is removed when dropped
+struct TempFile {
+    path: PathBuf,
+}
+
+/// Edit `text` in the user's editor, returning the edited text
+///
+/// The editor is taken from `$VISUAL` or `$EDITOR`, falling back on `vi`. The terminal must
+/// not be in raw mode while the editor is running.
+pub fn edit(text: &str) -> Result<String, Error> {
+    let editor = env::var("VISUAL")
+        .or_else(|_err| env::var("EDITOR"))
+        .unwrap_or_else(|_err| "vi".to_string());
+    let mut args = editor.split_whitespace();
+    let program = args.next().unwrap_or("vi");
+
+    let (temp, mut file) = TempFile::create()?;
+    file.write_all(text.as_bytes())?;
+    drop(file);
+
+    let status = Command::new(program).args(args).arg(&temp.path).status()?;
+    if status.success() {
+        Ok(fs::read_to_string(&temp.path)?)
+    } else {
+        Err(Error::Editor(status))
+    }
+}
+
+impl TempFile {
+    /// Create a file with a random name that no other file or symlink already has
+    fn create() -> io::Result<(Self, File)> {
+        let dir = env::temp_dir();
+        loop {
+            // RandomState is randomly keyed, which makes the hash of nothing a random number
+            let id = RandomState::new().build_hasher().finish();
+            let path = dir.join(format!("lobsters-{:016x}.md", id));
+            match Self::open(&path) {
+                Ok(file) => return Ok((TempFile { path }, file)),
+                Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn open(path: &Path) -> io::Result<File> {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)
+    }
+
+    #[cfg(not(unix))]
+    fn open(path: &Path) -> io::Result<File> {
+        OpenOptions::new().write(true).create_new(true).open(path)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        // Ignore failure to clean up, the edited text is all that matters
+        let _ = fs::remove_file(&self.path);
+    }
+}
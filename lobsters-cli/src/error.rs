@@ -1,15 +1,19 @@
 use lobsters::url;
 use std::fmt;
 use std::io;
+use std::process::ExitStatus;
 
 #[derive(Debug)]
 pub struct ParseThemeError(pub String);
 
+#[derive(Debug)]
+pub struct ParseFeedError(pub String);
+
 #[derive(Debug)]
 pub enum Error {
     Lobsters(lobsters::Error),
-    InvalidDate(chrono::ParseError),
     NotATty,
+    Editor(ExitStatus),
 }
 
 impl From<lobsters::Error> for Error {
@@ -18,12 +22,6 @@ impl From<lobsters::Error> for Error {
     }
 }
 
-impl From<chrono::ParseError> for Error {
-    fn from(err: chrono::ParseError) -> Self {
-        Error::InvalidDate(err)
-    }
-}
-
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Self {
         Error::Lobsters(lobsters::Error::Io(err))
@@ -45,3 +43,14 @@ impl fmt::Display for ParseThemeError {
         )
     }
 }
+
+impl fmt::Display for ParseFeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "'{}' is not a valid feed. Options are: hottest, newest, active, recent, top, \
+             top/day, top/week, top/month or top/year",
+            self.0
+        )
+    }
+}
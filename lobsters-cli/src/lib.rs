@@ -1,6 +1,7 @@
 #![warn(rust_2018_idioms)]
 
 pub mod app;
+pub mod editor;
 pub mod error;
 pub mod render;
 pub mod text;
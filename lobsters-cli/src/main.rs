@@ -1,29 +1,47 @@
-use std::io::{stdin, stdout, Write};
+use std::collections::{HashSet, VecDeque};
+use std::io::{self, stdin, stdout, Write};
 use std::str::FromStr;
+use std::thread::sleep;
+use std::time::Duration;
 
+use chrono_humanize::HumanTime;
 use env_logger::Env;
-use futures::future::Future;
+use futures::future::{Either, Future};
+use futures::Stream;
+use log::debug;
 use structopt::StructOpt;
 use termion::cursor;
 use termion::event::Key;
 use termion::input::TermRead;
-use termion::raw::IntoRawMode;
-use termion::screen::AlternateScreen;
+use termion::raw::{IntoRawMode, RawTerminal};
+use termion::screen::{AlternateScreen, ToAlternateScreen, ToMainScreen};
 use tokio::runtime::Runtime;
 
-use lobsters::client::Page;
+use lobsters::client::{Feed, LoginState, ModerationFilter, Page, ReplyFilter, SearchOrder};
+use lobsters::models::{
+    Comment, CommentId, Message, MessageId, Moderation, ModerationTarget, NewComment,
+    NewInvitation, NewMessage, NewStory, ShortTag,
+};
 use lobsters::url::Url;
-use lobsters::Client;
+use lobsters::{Client, ClientBuilder};
 
 use lobsters_cli::{
-    app::State,
+    app::{Filters, Inbox, Profile, Reader, Scroll, State, Thread},
+    editor,
     error::{Error, ParseThemeError},
-    render::{render_lines, render_stories},
-    theme::themes::*,
+    render::{
+        render_filters, render_inbox, render_lines, render_message, render_profile, render_stories,
+        render_thread, Lines,
+    },
+    theme::{themes::*, Theme},
     util,
 };
 
 const HORIZONTAL_SCROLL_AMOUNT: usize = 10;
+/// The most pages of new comments fetched by each check when following comments
+const FOLLOW_MAX_PAGES: u32 = 10;
+/// The number of comments remembered when following, to recognise those already printed
+const FOLLOW_MEMORY: usize = 1000;
 
 #[derive(Debug, StructOpt)]
 struct App {
@@ -36,6 +54,10 @@ struct App {
     )]
     base_url: Url,
 
+    /// Name of the profile to use, each profile has its own session
+    #[structopt(short = "P", long = "profile", default_value = "default")]
+    profile: String,
+
     #[structopt(subcommand)]
     command: Option<Command>,
 }
@@ -45,6 +67,27 @@ enum Command {
     /// Login with username and password to customise view
     #[structopt(name = "login")]
     Login(Login),
+    /// Logout and forget the saved session
+    #[structopt(name = "logout")]
+    Logout(Logout),
+    /// Show which user the saved session belongs to
+    #[structopt(name = "whoami")]
+    Whoami(Whoami),
+    /// Submit a new story
+    #[structopt(name = "submit")]
+    Submit(Submit),
+    /// List, read, send and delete private messages
+    #[structopt(name = "messages")]
+    Messages(Messages),
+    /// Invite someone to join the site by email
+    #[structopt(name = "invite")]
+    Invite(Invite),
+    /// Print the newest comments across all stories
+    #[structopt(name = "comments")]
+    Comments(Comments),
+    /// Print the moderation log
+    #[structopt(name = "modlog")]
+    Modlog(Modlog),
     /// View front page stories (this is the default)
     #[structopt(name = "stories")]
     Stories(Stories),
@@ -53,12 +96,158 @@ enum Command {
 #[derive(Debug, StructOpt)]
 struct Login {}
 
+#[derive(Debug, StructOpt)]
+struct Logout {}
+
+#[derive(Debug, StructOpt)]
+struct Whoami {}
+
+#[derive(Debug, StructOpt)]
+struct Submit {
+    /// Title of the story
+    #[structopt(long = "title")]
+    title: String,
+
+    /// URL of the story, may be omitted if text is supplied
+    #[structopt(short = "u", long = "url")]
+    url: Option<String>,
+
+    /// Text of the story, or a description of the URL
+    #[structopt(short = "d", long = "description")]
+    description: Option<String>,
+
+    /// Tag to apply to the story, may be given multiple times
+    #[structopt(short = "t", long = "tag")]
+    tags: Vec<String>,
+
+    /// You are the author of the story
+    #[structopt(short = "a", long = "authored")]
+    user_is_author: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct Messages {
+    #[structopt(subcommand)]
+    command: Option<MessagesCommand>,
+}
+
+#[derive(Debug, StructOpt)]
+enum MessagesCommand {
+    /// List the messages in the inbox (this is the default)
+    #[structopt(name = "list")]
+    List(ListMessages),
+    /// Print a message, marking it as read
+    #[structopt(name = "read")]
+    Read(ReadMessage),
+    /// Send a message
+    #[structopt(name = "send")]
+    Send(SendMessage),
+    /// Delete a message
+    #[structopt(name = "delete")]
+    Delete(DeleteMessage),
+}
+
+#[derive(Debug, Default, StructOpt)]
+struct ListMessages {
+    /// List sent messages instead of the inbox
+    #[structopt(short = "s", long = "sent")]
+    sent: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct ReadMessage {
+    /// Id of the message
+    id: String,
+}
+
+#[derive(Debug, StructOpt)]
+struct SendMessage {
+    /// Username of the recipient
+    #[structopt(short = "t", long = "to")]
+    to: String,
+
+    /// Subject of the message
+    #[structopt(short = "s", long = "subject")]
+    subject: String,
+
+    /// Body of the message, $EDITOR is opened to write it if omitted
+    #[structopt(short = "b", long = "body")]
+    body: Option<String>,
+}
+
+#[derive(Debug, StructOpt)]
+struct DeleteMessage {
+    /// Id of the message
+    id: String,
+}
+
+#[derive(Debug, StructOpt)]
+struct Invite {
+    /// Email address to send the invitation to
+    email: String,
+
+    /// Note to include in the invitation
+    #[structopt(short = "m", long = "memo", default_value = "")]
+    memo: String,
+}
+
+#[derive(Debug, StructOpt)]
+struct Comments {
+    /// Page to print
+    #[structopt(short = "p", long = "page")]
+    page: Option<u32>,
+
+    /// Keep running, printing new comments as they are posted
+    #[structopt(short = "f", long = "follow")]
+    follow: bool,
+
+    /// Seconds to wait between checks for new comments when following
+    #[structopt(short = "i", long = "interval", default_value = "60")]
+    interval: u64,
+}
+
+#[derive(Debug, StructOpt)]
+struct Modlog {
+    /// Page to print
+    #[structopt(short = "p", long = "page")]
+    page: Option<u32>,
+
+    /// Only print actions taken by this moderator
+    #[structopt(short = "m", long = "moderator")]
+    moderator: Option<String>,
+
+    /// Include actions on stories, all kinds are included if none are given
+    #[structopt(long = "stories")]
+    stories: bool,
+
+    /// Include actions on comments
+    #[structopt(long = "comments")]
+    comments: bool,
+
+    /// Include actions on tags
+    #[structopt(long = "tags")]
+    tags: bool,
+
+    /// Include actions on users
+    #[structopt(long = "users")]
+    users: bool,
+
+    /// Print the log as JSON instead of a table
+    #[structopt(short = "j", long = "json")]
+    json: bool,
+}
+
 #[derive(Debug, Default, StructOpt)]
 struct Stories {
     /// Page to view
     #[structopt(short = "p", long = "page")]
     page: Option<u32>,
 
+    /// Feed to view. Options: hottest (the default), newest, active, recent, top, top/day,
+    /// top/week, top/month, top/year
+    #[structopt(short = "f", long = "feed", parse(try_from_str = "util::parse_feed"))]
+    feed: Option<Feed>,
+
     /// Theme to use. Options: true, 256, grey or gray, mono
     #[structopt(
         short = "t",
@@ -69,9 +258,10 @@ struct Stories {
     theme: UiTheme,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 enum UiTheme {
     Grey,
+    #[default]
     Color256,
     Mono,
     TrueColor,
@@ -85,10 +275,21 @@ fn main() {
 
     let app = App::from_args();
     let mut rt = Runtime::new().unwrap();
-    let client = Client::new(app.base_url).expect("error creating client");
+    let client = ClientBuilder::new(app.base_url)
+        .profile(app.profile)
+        .user_agent(concat!("lobsters-cli/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .expect("error creating client");
 
     let result = match app.command.unwrap_or_default() {
         Command::Login(options) => login(&mut rt, client, options),
+        Command::Logout(options) => logout(&mut rt, client, options),
+        Command::Whoami(options) => whoami(&mut rt, client, options),
+        Command::Submit(options) => submit(&mut rt, client, options),
+        Command::Messages(options) => messages(&mut rt, client, options),
+        Command::Invite(options) => invite(&mut rt, client, options),
+        Command::Comments(options) => comments(&mut rt, client, options),
+        Command::Modlog(options) => modlog(&mut rt, client, options),
         Command::Stories(options) => stories(&mut rt, client, options),
     };
 
@@ -100,6 +301,12 @@ fn main() {
         Err(Error::Lobsters(lobsters::Error::InvalidStr)) => {
             eprintln!("UTF-8 error: Some data that was supposed to be a string was not valid UTF-8")
         }
+        Err(Error::Lobsters(lobsters::Error::Json(err))) => {
+            eprintln!("Error: Unable to parse response, caused by: {:?}", err)
+        }
+        Err(Error::Lobsters(lobsters::Error::FormEncoding)) => {
+            eprintln!("Error: Unable to encode form data for request")
+        }
         Err(Error::Lobsters(lobsters::Error::Io(err))) => {
             eprintln!("IO error, caused by: {:?}", err)
         }
@@ -114,10 +321,33 @@ fn main() {
             eprintln!("Error: Tried to find a HTML element that did not exist on the page")
         }
         Err(Error::Lobsters(lobsters::Error::Authorisation)) => eprintln!("Error: Not authorised"),
-        Err(Error::InvalidDate(err)) => eprintln!("Unable to parse date: {:?}", err),
+        Err(Error::Lobsters(lobsters::Error::Rejected(message))) => {
+            eprintln!("Error: Request rejected: {}", message)
+        }
+        Err(Error::Lobsters(lobsters::Error::RateLimited)) => {
+            eprintln!("Error: Too many requests, try again later")
+        }
+        Err(Error::Lobsters(lobsters::Error::NotFound)) => eprintln!("Error: Not found"),
+        Err(Error::Lobsters(lobsters::Error::Validation(errors))) => {
+            eprintln!("Error: Request rejected:");
+            for error in errors {
+                eprintln!("  {}", error);
+            }
+        }
+        Err(Error::Lobsters(lobsters::Error::InvalidHeaderValue)) => {
+            eprintln!("Error: A HTTP header value contained invalid characters")
+        }
+        Err(Error::Lobsters(lobsters::Error::NoTags)) => {
+            eprintln!("Error: No tags were given to list stories by")
+        }
+        Err(Error::Lobsters(lobsters::Error::InvalidProfile(profile))) => eprintln!(
+            "Error: '{}' is not a valid profile name, use letters, numbers, '-', '_' or '.'",
+            profile
+        ),
         Err(Error::NotATty) => {
             eprintln!("Error: This program needs a tty (you can't pipe or redirect its output)")
         }
+        Err(Error::Editor(status)) => eprintln!("Error: Editor exited with {}", status),
     }
 
     if result.is_err() {
@@ -151,30 +381,390 @@ fn login(rt: &mut Runtime, client: Client, _options: Login) -> CommandResult {
     // NOTE: unwrap is safe due to is_none checks above
     let login = client.login(username.unwrap(), password.unwrap());
 
-    let res = rt.block_on(login);
+    let res = match rt.block_on(login) {
+        Ok(LoginState::LoggedIn) => Ok(()),
+        Ok(LoginState::TwoFactorRequired(challenge)) => {
+            stdout.write_all(b"TOTP code: ")?;
+            stdout.flush()?;
+
+            let totp_code = stdin.read_line()?;
+            if totp_code.is_none() {
+                return Ok(());
+            }
+
+            // NOTE: unwrap is safe due to is_none check above
+            let totp_code = totp_code.unwrap().trim().to_string();
+            rt.block_on(client.login_2fa(challenge, totp_code))
+        }
+        Err(err) => Err(err),
+    };
+
+    res?;
+
+    // Persist the session so that subsequent runs are logged in
+    client.save_cookies()?;
+
+    // Ignore result since they have successfully logged in, no point showing an error now
+    let _ = stdout.write_all(b"Ok\n");
+
+    Ok(())
+}
+
+fn logout(rt: &mut Runtime, client: Client, _options: Logout) -> CommandResult {
+    rt.block_on(client.logout())?;
+    client.save_cookies()?;
+    println!("Logged out");
+
+    Ok(())
+}
+
+fn whoami(rt: &mut Runtime, client: Client, _options: Whoami) -> CommandResult {
+    match rt.block_on(client.whoami())? {
+        Some(username) => println!("{}", username),
+        None => println!("Not logged in"),
+    }
+
+    Ok(())
+}
+
+fn submit(rt: &mut Runtime, client: Client, options: Submit) -> CommandResult {
+    let story = NewStory {
+        url: options.url,
+        title: options.title,
+        description: options.description,
+        tags: options.tags.into_iter().map(ShortTag).collect(),
+        user_is_author: options.user_is_author,
+    };
+
+    let story = rt.block_on(client.submit_story(story))?;
+    println!("{}", story.comments_url);
+
+    Ok(())
+}
+
+fn messages(rt: &mut Runtime, client: Client, options: Messages) -> CommandResult {
+    let command = options
+        .command
+        .unwrap_or_else(|| MessagesCommand::List(ListMessages::default()));
+
+    match command {
+        MessagesCommand::List(options) => {
+            let messages = if options.sent {
+                rt.block_on(client.sent_messages())?
+            } else {
+                rt.block_on(client.inbox())?
+            };
+
+            for message in messages {
+                let created_at = message.created_at.to_datetime();
+                let who = if options.sent {
+                    format!("to {}", message.recipient_username)
+                } else {
+                    format!(
+                        "from {}",
+                        message.author_username.as_deref().unwrap_or("the system")
+                    )
+                };
+                println!(
+                    "{unread} {id}  {subject} ({who}, {when})",
+                    unread = if message.has_been_read { " " } else { "*" },
+                    id = message.short_id.0,
+                    subject = message.subject,
+                    who = who,
+                    when = HumanTime::from(created_at),
+                );
+            }
+        }
+        MessagesCommand::Read(options) => {
+            let thread = rt.block_on(client.message(&MessageId(options.id)))?;
+            for (i, message) in thread.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                print_message(message)?;
+            }
+        }
+        MessagesCommand::Send(options) => {
+            let body = match options.body {
+                Some(body) => body,
+                None => editor::edit("")?,
+            };
+            if body.trim().is_empty() {
+                println!("Message is empty, not sent");
+                return Ok(());
+            }
 
-    if res.is_ok() {
-        // Ignore result since they have successfully logged in, no point showing an error now
-        let _ = stdout.write_all(b"Ok\n");
+            let message = NewMessage {
+                recipient_username: options.to,
+                subject: options.subject,
+                body,
+                hat_id: None,
+            };
+            rt.block_on(client.send_message(message))?;
+            println!("Sent");
+        }
+        MessagesCommand::Delete(options) => {
+            rt.block_on(client.delete_message(&MessageId(options.id)))?;
+            println!("Deleted");
+        }
     }
 
-    res.map_err(Error::from)
+    Ok(())
+}
+
+fn print_message(message: &Message) -> CommandResult {
+    let created_at = message.created_at.to_datetime();
+    println!("Subject: {}", message.subject);
+    println!(
+        "From: {}",
+        message.author_username.as_deref().unwrap_or("the system")
+    );
+    println!("To: {}", message.recipient_username);
+    println!("Date: {}", created_at.to_rfc2822());
+    println!();
+    println!("{}", message.body);
+
+    Ok(())
+}
+
+fn invite(rt: &mut Runtime, client: Client, options: Invite) -> CommandResult {
+    let invitation = NewInvitation {
+        email: options.email,
+        memo: options.memo,
+    };
+    let email = invitation.email.clone();
+    rt.block_on(client.send_invitation(invitation))?;
+    println!("Invitation sent to {}", email);
+
+    Ok(())
+}
+
+fn comments(rt: &mut Runtime, client: Client, options: Comments) -> CommandResult {
+    let page = Page::new(options.page.unwrap_or(1));
+    let comments = rt.block_on(client.recent_comments(page))?;
+
+    // Print oldest first so that the newest comment ends up at the bottom
+    let mut seen = SeenComments::default();
+    for comment in comments.iter().rev() {
+        print_comment(comment);
+        seen.insert(comment.short_id.clone());
+    }
+
+    if !options.follow {
+        return Ok(());
+    }
+
+    'follow: loop {
+        sleep(Duration::from_secs(options.interval));
+
+        // Fetch pages until reaching comments that have been printed already
+        let mut comments = Vec::new();
+        let mut caught_up = false;
+        for page in 1..=FOLLOW_MAX_PAGES {
+            // Keep following through temporary problems fetching the comments, the comments
+            // that were missed are fetched again by the next check
+            let page_comments = match rt.block_on(client.recent_comments(Page::new(page))) {
+                Ok(comments) => comments,
+                Err(err) => {
+                    eprintln!("{}", error_status("Unable to fetch comments", &err));
+                    continue 'follow;
+                }
+            };
+
+            caught_up = page_comments.is_empty()
+                || page_comments
+                    .iter()
+                    .any(|comment| seen.contains(&comment.short_id));
+            comments.extend(page_comments);
+            if caught_up {
+                break;
+            }
+        }
+        if !caught_up {
+            eprintln!(
+                "Warning: More than {} pages of new comments, older ones were skipped",
+                FOLLOW_MAX_PAGES
+            );
+        }
+
+        for comment in comments.iter().rev() {
+            if seen.insert(comment.short_id.clone()) {
+                print_comment(comment);
+            }
+        }
+    }
+}
+
+/// The most recently printed comments, forgetting the oldest past `FOLLOW_MEMORY`
+#[derive(Default)]
+struct SeenComments {
+    ids: HashSet<CommentId>,
+    order: VecDeque<CommentId>,
+}
+
+impl SeenComments {
+    fn contains(&self, comment_id: &CommentId) -> bool {
+        self.ids.contains(comment_id)
+    }
+
+    /// Remember `comment_id`, returning `false` if it was already seen
+    fn insert(&mut self, comment_id: CommentId) -> bool {
+        if !self.ids.insert(comment_id.clone()) {
+            return false;
+        }
+
+        self.order.push_back(comment_id);
+        if self.order.len() > FOLLOW_MEMORY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+fn print_comment(comment: &Comment) {
+    let story = comment
+        .story_title
+        .as_ref()
+        .or_else(|| comment.story_id.as_ref().map(|story_id| &story_id.0));
+    match story {
+        Some(story) => println!("{} on {}", comment.commenting_user.username, story),
+        None => println!("{}", comment.commenting_user.username),
+    }
+    println!("{}", comment.short_id_url);
+    println!();
+    println!("{}", util::html_to_text(&comment.comment));
+    println!();
+}
+
+fn modlog(rt: &mut Runtime, client: Client, options: Modlog) -> CommandResult {
+    let targets = [
+        (options.stories, ModerationTarget::Story),
+        (options.comments, ModerationTarget::Comment),
+        (options.tags, ModerationTarget::Tag),
+        (options.users, ModerationTarget::User),
+    ];
+    let filter = ModerationFilter {
+        moderator: options.moderator,
+        targets: targets
+            .iter()
+            .filter(|(included, _)| *included)
+            .map(|(_, target)| *target)
+            .collect(),
+    };
+    let page = Page::new(options.page.unwrap_or(1));
+    let moderations = rt.block_on(client.moderations(&filter, page))?;
+
+    if options.json {
+        let json = serde_json::to_string_pretty(&moderations).map_err(lobsters::Error::Json)?;
+        println!("{}", json);
+    } else {
+        print_moderations(&moderations);
+    }
+
+    Ok(())
+}
+
+/// Print the moderation log as a table, with the reason for an action on the line below it
+fn print_moderations(moderations: &[Moderation]) {
+    const MAX_TARGET_WIDTH: usize = 50;
+
+    let moderator_width = moderations
+        .iter()
+        .filter_map(|moderation| moderation.moderator.as_ref())
+        .map(|moderator| moderator.chars().count())
+        .chain(Some("MODERATOR".len()))
+        .max()
+        .unwrap_or(0);
+    let target_width = moderations
+        .iter()
+        .map(|moderation| moderation.target_description.chars().count())
+        .chain(Some("TARGET".len()))
+        .max()
+        .unwrap_or(0)
+        .min(MAX_TARGET_WIDTH);
+    let date_width = moderations
+        .iter()
+        .map(|moderation| moderation.created_at.chars().count())
+        .chain(Some("DATE".len()))
+        .max()
+        .unwrap_or(0);
+
+    println!(
+        "{:date$}  {:moderator$}  {:target$}  ACTION",
+        "DATE",
+        "MODERATOR",
+        "TARGET",
+        date = date_width,
+        moderator = moderator_width,
+        target = target_width,
+    );
+    for moderation in moderations {
+        let mut target = moderation.target_description.clone();
+        if target.chars().count() > target_width {
+            target = target.chars().take(target_width - 1).collect();
+            target.push('…');
+        }
+        println!(
+            "{:date$}  {:moderator$}  {:target$}  {}",
+            moderation.created_at,
+            moderation.moderator.as_ref().map_or("-", String::as_str),
+            target,
+            moderation.action,
+            date = date_width,
+            moderator = moderator_width,
+            target = target_width,
+        );
+        if let Some(reason) = &moderation.reason {
+            println!(
+                "{:indent$}  Reason: {}",
+                "",
+                reason,
+                indent = date_width + moderator_width + target_width + 4,
+            );
+        }
+    }
 }
 
 fn stories(rt: &mut Runtime, client: Client, options: Stories) -> CommandResult {
     let page = Page::new(options.page.unwrap_or(1));
-    let future_stories = client.index(page);
+    let feed = options.feed.unwrap_or(Feed::Hottest);
+    let future_stories = client.stories(feed, page);
     let future_tags = client.tags();
-    let work = future_tags.join(future_stories);
+    // Only the first page of unread replies is counted, failure leaves the count unknown as
+    // it's expected when not logged in
+    let future_unread = client
+        .replies(ReplyFilter::Unread, None)
+        .then(|replies| match replies {
+            Ok(replies) => Ok(Some(replies.len())),
+            Err(err) => {
+                debug!("unable to count unread replies: {:?}", err);
+                Ok(None)
+            }
+        });
+    // The saved stories are used to show which stories are saved, so every page is fetched.
+    // Not being logged in or a failure only means no stories are shown as saved.
+    let future_saved = client
+        .saved_story_stream(2)
+        .map(|story| story.short_id)
+        .collect()
+        .then(|story_ids| {
+            if let Err(err) = &story_ids {
+                debug!("unable to fetch saved stories: {:?}", err);
+            }
+            Ok(story_ids.unwrap_or_default().into_iter().collect())
+        });
+    let work = future_tags.join4(future_stories, future_unread, future_saved);
 
     if !termion::is_tty(&stdout()) {
         return Err(Error::NotATty);
     }
 
-    // Fetch tags and stories in parallel
+    // Fetch tags, stories, unread replies and saved stories in parallel
     print!("Loading...");
     stdout().flush()?;
-    let (tags, stories) = rt.block_on(work)?;
+    let (tags, stories, unread_replies, saved) = rt.block_on(work)?;
     println!(" done.");
 
     let (_width, height) = util::as_usize(termion::terminal_size()?);
@@ -185,6 +775,8 @@ fn stories(rt: &mut Runtime, client: Client, options: Stories) -> CommandResult
     }
 
     let mut state = State::new(stories, tags);
+    state.set_unread_replies(unread_replies);
+    state.set_saved_stories(saved);
 
     // Switch to alternate screen and enter main loop
     {
@@ -202,53 +794,813 @@ fn stories(rt: &mut Runtime, client: Client, options: Stories) -> CommandResult
             UiTheme::Grey => &LOBSTERS_GREY,
         };
 
-        // Render initial UI
-        let mut lines = render_stories(&mut state, theme, height)?;
-        render_lines(&lines, &mut screen, state.col_offset())?;
-
-        // Main loop
-        for c in stdin.keys() {
-            match c.unwrap() {
-                Key::Char('q') | Key::Esc => break,
-                Key::Char('j') | Key::Down => {
-                    if state.next_story() {
-                        lines = render_stories(&mut state, theme, height)?;
-                        render_lines(&lines, &mut screen, state.col_offset())?;
+        let mut keys = stdin.keys();
+        story_list(
+            rt,
+            &client,
+            &mut screen,
+            &mut keys,
+            theme,
+            &mut state,
+            height,
+        )?;
+
+        // Restore the cursor before returning
+        write!(screen, "{}", cursor::Show)?;
+    }
+
+    Ok(())
+}
+
+/// Show a list of stories until the user quits or returns to the previous list
+fn story_list<W, K>(
+    rt: &mut Runtime,
+    client: &Client,
+    screen: &mut RawTerminal<W>,
+    keys: &mut K,
+    theme: &Theme,
+    state: &mut State,
+    height: usize,
+) -> CommandResult
+where
+    W: Write,
+    K: Iterator<Item = io::Result<Key>>,
+{
+    // Render initial UI
+    let mut lines = render_stories(state, theme, height)?;
+    render_lines(&lines, screen, state.col_offset())?;
+
+    while let Some(c) = keys.next() {
+        state.clear_status();
+
+        match c? {
+            Key::Char('q') | Key::Esc => break,
+            Key::Char('j') | Key::Down if state.next_story() => {
+                lines = render_stories(state, theme, height)?;
+                render_lines(&lines, screen, state.col_offset())?;
+            }
+            Key::Char('k') | Key::Up if state.prev_story() => {
+                lines = render_stories(state, theme, height)?;
+                render_lines(&lines, screen, state.col_offset())?;
+            }
+            Key::Char('h') | Key::Left if state.scroll_left(HORIZONTAL_SCROLL_AMOUNT) => {
+                render_lines(&lines, screen, state.col_offset())?;
+            }
+            Key::Char('l') | Key::Right if state.scroll_right(HORIZONTAL_SCROLL_AMOUNT) => {
+                render_lines(&lines, screen, state.col_offset())?;
+            }
+            Key::Char('c') => {
+                let _ = opener::open(state.comments_url()?.as_str());
+            }
+            Key::Char('\n') => {
+                let _ = opener::open(state.story_url()?.as_str());
+            }
+            Key::Char('u') => {
+                let story_id = state.current_story().short_id.clone();
+                let upvoted = state.is_upvoted();
+                let vote = if upvoted {
+                    Either::A(client.unvote_story(&story_id))
+                } else {
+                    Either::B(client.upvote_story(&story_id))
+                };
+
+                match rt.block_on(vote) {
+                    Ok(()) => {
+                        state.set_upvoted(!upvoted);
+                        state.set_status(if upvoted { "Vote removed" } else { "Upvoted" });
                     }
+                    Err(err) => state.set_status(error_status("Unable to vote", &err)),
                 }
-                Key::Char('k') | Key::Up => {
-                    if state.prev_story() {
-                        lines = render_stories(&mut state, theme, height)?;
-                        render_lines(&lines, &mut screen, state.col_offset())?;
+                lines = render_stories(state, theme, height)?;
+                render_lines(&lines, screen, state.col_offset())?;
+            }
+            Key::Char('s') => {
+                let story_id = state.current_story().short_id.clone();
+                let saved = state.is_saved(&story_id);
+                let save = if saved {
+                    Either::A(client.unsave_story(&story_id))
+                } else {
+                    Either::B(client.save_story(&story_id))
+                };
+
+                match rt.block_on(save) {
+                    Ok(()) => {
+                        state.set_saved(!saved);
+                        state.set_status(if saved {
+                            "Story unsaved"
+                        } else {
+                            "Story saved"
+                        });
                     }
+                    Err(err) => state.set_status(error_status("Unable to save story", &err)),
                 }
-                Key::Char('h') | Key::Left => {
-                    if state.scroll_left(HORIZONTAL_SCROLL_AMOUNT) {
-                        render_lines(&lines, &mut screen, state.col_offset())?;
+                lines = render_stories(state, theme, height)?;
+                render_lines(&lines, screen, state.col_offset())?;
+            }
+            Key::Char('x') => {
+                let story_id = state.current_story().short_id.clone();
+                let hidden = state.is_hidden(&story_id);
+                let hide = if hidden {
+                    Either::A(client.unhide_story(&story_id))
+                } else {
+                    Either::B(client.hide_story(&story_id))
+                };
+
+                match rt.block_on(hide) {
+                    Ok(()) => {
+                        state.set_hidden(!hidden);
+                        state.set_status(if hidden {
+                            "Story unhidden"
+                        } else {
+                            "Story hidden"
+                        });
                     }
+                    Err(err) => state.set_status(error_status("Unable to hide story", &err)),
                 }
-                Key::Char('l') | Key::Right => {
-                    if state.scroll_right(HORIZONTAL_SCROLL_AMOUNT) {
-                        render_lines(&lines, &mut screen, state.col_offset())?;
+                lines = render_stories(state, theme, height)?;
+                render_lines(&lines, screen, state.col_offset())?;
+            }
+            Key::Char('t') => {
+                let story_id = state.current_story().short_id.clone();
+                let work = client.story(&story_id).join(client.whoami());
+
+                match rt.block_on(work) {
+                    Ok((story, username)) => {
+                        let mut current = Thread::new(story, username);
+                        thread(rt, client, screen, keys, theme, &mut current)?;
                     }
+                    Err(err) => state.set_status(error_status("Unable to load comments", &err)),
                 }
-                Key::Char('c') => {
-                    let _ = opener::open(state.comments_url()?.as_str());
+                lines = render_stories(state, theme, height)?;
+                render_lines(&lines, screen, state.col_offset())?;
+            }
+            Key::Char('p') => {
+                let username = state.current_story().submitter_user.username.clone();
+                match rt.block_on(load_profile(client, &username)) {
+                    Ok(mut current) => profile(screen, keys, theme, &mut current)?,
+                    Err(err) => state.set_status(error_status("Unable to load profile", &err)),
                 }
-                Key::Char('\n') => {
-                    let _ = opener::open(state.story_url()?.as_str());
+                lines = render_stories(state, theme, height)?;
+                render_lines(&lines, screen, state.col_offset())?;
+            }
+            Key::Char('m') => {
+                match rt.block_on(client.inbox()) {
+                    Ok(messages) => {
+                        let mut current = Inbox::new(messages);
+                        inbox(rt, client, screen, keys, theme, &mut current)?;
+                    }
+                    Err(err) => state.set_status(error_status("Unable to load messages", &err)),
                 }
-                _ => (),
+                lines = render_stories(state, theme, height)?;
+                render_lines(&lines, screen, state.col_offset())?;
             }
+            Key::Char('f') => {
+                match rt.block_on(client.filtered_tags()) {
+                    Ok(filtered) => {
+                        let mut current = Filters::new(state.tag_map(), filtered);
+                        filters(rt, client, screen, keys, theme, &mut current)?;
+                    }
+                    Err(err) => state.set_status(error_status("Unable to load filters", &err)),
+                }
+                lines = render_stories(state, theme, height)?;
+                render_lines(&lines, screen, state.col_offset())?;
+            }
+            Key::Char('/') => {
+                if let Some(query) = prompt(screen, keys, theme, state, height, "Search: ")? {
+                    let search = client.search_stories(&query, SearchOrder::Relevance, None);
+                    match rt.block_on(search) {
+                        Ok(stories) => match state
+                            .with_stories(stories, format!("Search results for {}", query))
+                        {
+                            Some(mut results) => {
+                                story_list(rt, client, screen, keys, theme, &mut results, height)?;
+                            }
+                            None => state.set_status(format!("No stories found for {}", query)),
+                        },
+                        Err(err) => state.set_status(error_status("Unable to search", &err)),
+                    }
+                }
+                lines = render_stories(state, theme, height)?;
+                render_lines(&lines, screen, state.col_offset())?;
+            }
+            Key::Char(key @ 'T') | Key::Char(key @ 'D') => {
+                let story = state.current_story();
+                let listing = if key == 'T' {
+                    let tags = story.tags.clone();
+                    let names: Vec<_> = tags.iter().map(|tag| tag.0.as_str()).collect();
+                    let title = format!("Stories tagged {}", names.join(", "));
+                    Some((title, Either::A(client.tagged_stories(&tags, None))))
+                } else {
+                    state.story_domain().map(|domain| {
+                        let title = format!("Stories from {}", domain);
+                        (title, Either::B(client.domain_stories(&domain, None)))
+                    })
+                };
+
+                match listing.map(|(title, work)| (title, rt.block_on(work))) {
+                    Some((title, Ok(stories))) => match state.with_stories(stories, title) {
+                        Some(mut listing) => {
+                            story_list(rt, client, screen, keys, theme, &mut listing, height)?;
+                        }
+                        None => state.set_status("There are no stories to show"),
+                    },
+                    Some((_, Err(err))) => {
+                        state.set_status(error_status("Unable to load stories", &err))
+                    }
+                    None => state.set_status("Story has no domain"),
+                }
+                lines = render_stories(state, theme, height)?;
+                render_lines(&lines, screen, state.col_offset())?;
+            }
+            _ => (),
         }
+    }
 
-        // Restore the cursor before returning
-        write!(screen, "{}", cursor::Show)?;
+    Ok(())
+}
+
+/// Show the comments of a story until the user returns to the stories view
+fn thread<W, K>(
+    rt: &mut Runtime,
+    client: &Client,
+    screen: &mut RawTerminal<W>,
+    keys: &mut K,
+    theme: &Theme,
+    thread: &mut Thread,
+) -> CommandResult
+where
+    W: Write,
+    K: Iterator<Item = io::Result<Key>>,
+{
+    let (width, height) = util::as_usize(termion::terminal_size()?);
+    let lines = render_thread(thread, theme, width, height)?;
+    render_lines(&lines, screen, 0)?;
+
+    while let Some(c) = keys.next() {
+        thread.clear_status();
+
+        match c? {
+            Key::Char('q') | Key::Esc => break,
+            Key::Char('j') | Key::Down => {
+                if !thread.next_comment() {
+                    continue;
+                }
+            }
+            Key::Char('k') | Key::Up => {
+                if !thread.prev_comment() {
+                    continue;
+                }
+            }
+            Key::Char('c') => write_comment(rt, client, screen, keys, theme, thread, false)?,
+            Key::Char('r') => write_comment(rt, client, screen, keys, theme, thread, true)?,
+            Key::Char('e') => edit_comment(rt, client, screen, thread)?,
+            Key::Char('p') => {
+                let username = match thread.current_comment() {
+                    Some(comment) => comment.commenting_user.username.clone(),
+                    None => thread.story().submitter_user.username.clone(),
+                };
+                match rt.block_on(load_profile(client, &username)) {
+                    Ok(mut current) => profile(screen, keys, theme, &mut current)?,
+                    Err(err) => thread.set_status(error_status("Unable to load profile", &err)),
+                }
+            }
+            _ => continue,
+        }
+
+        let lines = render_thread(thread, theme, width, height)?;
+        render_lines(&lines, screen, 0)?;
+    }
+
+    Ok(())
+}
+
+/// Edit the tags filtered out of listings until the user returns to the stories view
+fn filters<W, K>(
+    rt: &mut Runtime,
+    client: &Client,
+    screen: &mut RawTerminal<W>,
+    keys: &mut K,
+    theme: &Theme,
+    filters: &mut Filters,
+) -> CommandResult
+where
+    W: Write,
+    K: Iterator<Item = io::Result<Key>>,
+{
+    let (_width, height) = util::as_usize(termion::terminal_size()?);
+    let lines = render_filters(filters, theme, height);
+    render_lines(&lines, screen, 0)?;
+
+    let mut confirm_discard = false;
+    for c in keys {
+        filters.clear_status();
+
+        match c? {
+            Key::Char('q') | Key::Esc if filters.is_modified() && !confirm_discard => {
+                filters.set_status("Filters not saved, press w to save or q again to discard");
+                confirm_discard = true;
+                let lines = render_filters(filters, theme, height);
+                render_lines(&lines, screen, 0)?;
+                continue;
+            }
+            Key::Char('q') | Key::Esc => break,
+            Key::Char('j') | Key::Down => {
+                if !filters.next_tag() {
+                    continue;
+                }
+            }
+            Key::Char('k') | Key::Up => {
+                if !filters.prev_tag() {
+                    continue;
+                }
+            }
+            Key::Char(' ') => filters.toggle_current(),
+            Key::Char('w') => {
+                let tags = filters.filtered_tags();
+                match rt.block_on(client.set_filtered_tags(&tags)) {
+                    Ok(()) => {
+                        filters.set_saved();
+                        filters
+                            .set_status("Filters saved, they apply when stories are next loaded");
+                    }
+                    Err(err) => filters.set_status(error_status("Unable to save filters", &err)),
+                }
+            }
+            _ => continue,
+        }
+        confirm_discard = false;
+
+        let lines = render_filters(filters, theme, height);
+        render_lines(&lines, screen, 0)?;
+    }
+
+    Ok(())
+}
+
+/// Show the messages in the inbox until the user returns to the stories view
+fn inbox<W, K>(
+    rt: &mut Runtime,
+    client: &Client,
+    screen: &mut RawTerminal<W>,
+    keys: &mut K,
+    theme: &Theme,
+    inbox: &mut Inbox,
+) -> CommandResult
+where
+    W: Write,
+    K: Iterator<Item = io::Result<Key>>,
+{
+    let (width, height) = util::as_usize(termion::terminal_size()?);
+    let lines = render_inbox(inbox, theme, height)?;
+    render_lines(&lines, screen, 0)?;
+
+    while let Some(c) = keys.next() {
+        inbox.clear_status();
+
+        match c? {
+            Key::Char('q') | Key::Esc => break,
+            Key::Char('j') | Key::Down => {
+                if !inbox.next_message() {
+                    continue;
+                }
+            }
+            Key::Char('k') | Key::Up => {
+                if !inbox.prev_message() {
+                    continue;
+                }
+            }
+            Key::Char('\n') => {
+                let message_id = match inbox.current_message() {
+                    Some(message) => message.short_id.clone(),
+                    None => continue,
+                };
+                match rt.block_on(client.message(&message_id)) {
+                    Ok(thread) => {
+                        inbox.mark_read();
+                        let mut reader = Reader::new(thread);
+                        read_message(screen, keys, theme, &mut reader, width, height)?;
+                    }
+                    Err(err) => inbox.set_status(error_status("Unable to load message", &err)),
+                }
+            }
+            Key::Char('r') => reply_to_message(rt, client, screen, inbox)?,
+            Key::Char('d') => {
+                let message_id = match inbox.current_message() {
+                    Some(message) => message.short_id.clone(),
+                    None => continue,
+                };
+                inbox.set_status("Delete this message? (y/n)");
+                let lines = render_inbox(inbox, theme, height)?;
+                render_lines(&lines, screen, 0)?;
+
+                inbox.clear_status();
+                if let Some(Key::Char('y')) = keys.next().transpose()? {
+                    match rt.block_on(client.delete_message(&message_id)) {
+                        Ok(()) => {
+                            inbox.remove_current();
+                            inbox.set_status("Message deleted");
+                        }
+                        Err(err) => {
+                            inbox.set_status(error_status("Unable to delete message", &err))
+                        }
+                    }
+                }
+            }
+            _ => continue,
+        }
+
+        let lines = render_inbox(inbox, theme, height)?;
+        render_lines(&lines, screen, 0)?;
+    }
+
+    Ok(())
+}
+
+/// Show a message until the user returns to the inbox
+fn read_message<W, K>(
+    screen: &mut RawTerminal<W>,
+    keys: &mut K,
+    theme: &Theme,
+    reader: &mut Reader,
+    width: usize,
+    height: usize,
+) -> CommandResult
+where
+    W: Write,
+    K: Iterator<Item = io::Result<Key>>,
+{
+    scroll_view(screen, keys, reader, |reader| {
+        render_message(reader, theme, width, height)
+    })
+}
+
+/// Write a reply to the current message of the inbox in the user's editor and send it
+fn reply_to_message<W: Write>(
+    rt: &mut Runtime,
+    client: &Client,
+    screen: &mut RawTerminal<W>,
+    inbox: &mut Inbox,
+) -> CommandResult {
+    let (recipient, subject) = match inbox.current_message() {
+        Some(message) => match &message.author_username {
+            Some(author) if message.subject.starts_with("Re:") => {
+                (author.clone(), message.subject.clone())
+            }
+            Some(author) => (author.clone(), format!("Re: {}", message.subject)),
+            None => {
+                inbox.set_status("Messages from the system can't be replied to");
+                return Ok(());
+            }
+        },
+        None => return Ok(()),
+    };
+
+    let body = match edit_in_terminal(screen, "") {
+        Ok(body) => body,
+        Err(Error::Editor(status)) => {
+            inbox.set_status(format!("Reply not sent, editor exited with {}", status));
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+    };
+    if body.trim().is_empty() {
+        inbox.set_status("Reply is empty, not sent");
+        return Ok(());
+    }
+
+    let message = NewMessage {
+        recipient_username: recipient,
+        subject,
+        body,
+        hat_id: None,
+    };
+    match rt.block_on(client.send_message(message)) {
+        Ok(()) => inbox.set_status("Reply sent"),
+        Err(err) => inbox.set_status(error_status("Unable to send reply", &err)),
     }
 
     Ok(())
 }
 
+/// Read a line of input in the status line of the story list
+///
+/// Returns `None` if the prompt is cancelled with `Esc` or the input is empty.
+fn prompt<W, K>(
+    screen: &mut RawTerminal<W>,
+    keys: &mut K,
+    theme: &Theme,
+    state: &mut State,
+    height: usize,
+    label: &str,
+) -> Result<Option<String>, Error>
+where
+    W: Write,
+    K: Iterator<Item = io::Result<Key>>,
+{
+    let mut input = String::new();
+
+    loop {
+        state.set_status(format!("{}{}", label, input));
+        let lines = render_stories(state, theme, height)?;
+        render_lines(&lines, screen, state.col_offset())?;
+
+        match keys.next().transpose()? {
+            Some(Key::Char('\n')) => break,
+            Some(Key::Char(c)) => input.push(c),
+            Some(Key::Backspace) => {
+                input.pop();
+            }
+            Some(Key::Esc) | None => {
+                input.clear();
+                break;
+            }
+            _ => (),
+        }
+    }
+    state.clear_status();
+
+    let input = input.trim();
+    if input.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(input.to_string()))
+    }
+}
+
+/// Fetch a user's profile along with their recent stories
+fn load_profile(
+    client: &Client,
+    username: &str,
+) -> impl Future<Item = Profile, Error = lobsters::Error> {
+    client
+        .user(username)
+        .join(client.user_stories(username, None))
+        .map(|(user, stories)| Profile::new(user, stories))
+}
+
+/// Show a user's profile until the user returns to the previous view
+fn profile<W, K>(
+    screen: &mut RawTerminal<W>,
+    keys: &mut K,
+    theme: &Theme,
+    profile: &mut Profile,
+) -> CommandResult
+where
+    W: Write,
+    K: Iterator<Item = io::Result<Key>>,
+{
+    let (width, height) = util::as_usize(termion::terminal_size()?);
+    scroll_view(screen, keys, profile, |profile| {
+        render_profile(profile, theme, width, height)
+    })
+}
+
+/// Show a view that scrolls with `j` and `k` until the user returns to the previous view
+fn scroll_view<W, K, V, R>(
+    screen: &mut RawTerminal<W>,
+    keys: &mut K,
+    view: &mut V,
+    render: R,
+) -> CommandResult
+where
+    W: Write,
+    K: Iterator<Item = io::Result<Key>>,
+    V: AsMut<Scroll>,
+    R: Fn(&mut V) -> Result<Lines, Error>,
+{
+    let lines = render(view)?;
+    render_lines(&lines, screen, 0)?;
+
+    for c in keys {
+        match c? {
+            Key::Char('q') | Key::Esc => break,
+            Key::Char('j') | Key::Down => view.as_mut().scroll_down(),
+            Key::Char('k') | Key::Up => {
+                if !view.as_mut().scroll_up() {
+                    continue;
+                }
+            }
+            _ => continue,
+        }
+
+        let lines = render(view)?;
+        render_lines(&lines, screen, 0)?;
+    }
+
+    Ok(())
+}
+
+/// Edit the current comment of the thread in the user's editor
+///
+/// Problems with the request are reported in the status line of the thread.
+fn edit_comment<W: Write>(
+    rt: &mut Runtime,
+    client: &Client,
+    screen: &mut RawTerminal<W>,
+    thread: &mut Thread,
+) -> CommandResult {
+    let comment_id = match thread.current_comment() {
+        Some(comment) if thread.is_own_comment() && !comment.is_deleted => comment.short_id.clone(),
+        _ => {
+            thread.set_status("Only your own comments can be edited");
+            return Ok(());
+        }
+    };
+
+    let source = match rt.block_on(client.comment_source(&comment_id)) {
+        Ok(source) => source,
+        Err(err) => {
+            thread.set_status(error_status("Unable to edit comment", &err));
+            return Ok(());
+        }
+    };
+
+    let text = match edit_in_terminal(screen, &source) {
+        Ok(text) => text,
+        Err(Error::Editor(status)) => {
+            thread.set_status(format!(
+                "Comment not updated, editor exited with {}",
+                status
+            ));
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+    };
+    if text.trim() == source.trim() {
+        thread.set_status("Comment unchanged");
+        return Ok(());
+    }
+
+    if let Err(err) = rt.block_on(client.update_comment(&comment_id, text)) {
+        thread.set_status(error_status("Unable to update comment", &err));
+        return Ok(());
+    }
+
+    // Fetch the story again to show the updated comment
+    let story_id = thread.story().short_id.clone();
+    match rt.block_on(client.story(&story_id)) {
+        Ok(story) => {
+            thread.set_story(story);
+            thread.set_status("Comment updated");
+        }
+        Err(err) => thread.set_status(error_status("Comment updated, unable to refresh", &err)),
+    }
+
+    Ok(())
+}
+
+/// Write a comment on the story of the thread in the user's editor and post it
+///
+/// When `reply` is set the comment replies to the current comment instead. Users that have hats
+/// are asked which one to wear first.
+fn write_comment<W, K>(
+    rt: &mut Runtime,
+    client: &Client,
+    screen: &mut RawTerminal<W>,
+    keys: &mut K,
+    theme: &Theme,
+    thread: &mut Thread,
+    reply: bool,
+) -> CommandResult
+where
+    W: Write,
+    K: Iterator<Item = io::Result<Key>>,
+{
+    let parent_comment_short_id = match thread.current_comment() {
+        Some(comment) if reply => Some(comment.short_id.clone()),
+        None if reply => {
+            thread.set_status("There is no comment to reply to");
+            return Ok(());
+        }
+        _ => None,
+    };
+
+    if thread.hats().is_none() {
+        match rt.block_on(client.wearable_hats()) {
+            Ok(hats) => thread.set_hats(hats),
+            Err(err) => {
+                thread.set_status(error_status("Unable to comment", &err));
+                return Ok(());
+            }
+        }
+    }
+    let hat_id = match pick_hat(screen, keys, theme, thread)? {
+        Some(hat_id) => hat_id,
+        None => return Ok(()),
+    };
+
+    let text = match edit_in_terminal(screen, "") {
+        Ok(text) => text,
+        Err(Error::Editor(status)) => {
+            thread.set_status(format!("Comment not posted, editor exited with {}", status));
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+    };
+    if text.trim().is_empty() {
+        thread.set_status("Comment is empty, not posted");
+        return Ok(());
+    }
+
+    let story_id = thread.story().short_id.clone();
+    let comment = NewComment {
+        story_id: story_id.clone(),
+        comment: text,
+        hat_id,
+        parent_comment_short_id,
+    };
+    if let Err(err) = rt.block_on(client.post_comment(comment)) {
+        thread.set_status(error_status("Unable to post comment", &err));
+        return Ok(());
+    }
+
+    // Fetch the story again to show the new comment
+    match rt.block_on(client.story(&story_id)) {
+        Ok(story) => {
+            thread.set_story(story);
+            thread.set_status("Comment posted");
+        }
+        Err(err) => thread.set_status(error_status("Comment posted, unable to refresh", &err)),
+    }
+
+    Ok(())
+}
+
+/// Ask which of their hats the user wants to wear, in the status line of the thread
+///
+/// Returns `None` if the comment is cancelled with `Esc`, or `Some` with the id of the chosen
+/// hat. Users without hats aren't asked.
+fn pick_hat<W, K>(
+    screen: &mut RawTerminal<W>,
+    keys: &mut K,
+    theme: &Theme,
+    thread: &mut Thread,
+) -> Result<Option<Option<String>>, Error>
+where
+    W: Write,
+    K: Iterator<Item = io::Result<Key>>,
+{
+    // Only the first nine hats can be picked with a single key
+    let hats: Vec<_> = match thread.hats() {
+        Some(hats) if !hats.is_empty() => hats
+            .iter()
+            .take(9)
+            .map(|hat| (hat.hat.clone(), hat.short_id.clone()))
+            .collect(),
+        _ => return Ok(Some(None)),
+    };
+
+    let choices: Vec<_> = hats
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _))| format!("{}: {}", i + 1, name))
+        .collect();
+    thread.set_status(format!("Wear a hat? 0: none, {}", choices.join(", ")));
+    let (width, height) = util::as_usize(termion::terminal_size()?);
+    let lines = render_thread(thread, theme, width, height)?;
+    render_lines(&lines, screen, 0)?;
+
+    let picked = loop {
+        match keys.next().transpose()? {
+            Some(Key::Char('0')) | Some(Key::Char('\n')) => break Some(None),
+            Some(Key::Char(c)) => {
+                let index = c.to_digit(10).map(|digit| digit as usize);
+                if let Some((_, short_id)) = index.and_then(|i| hats.get(i.wrapping_sub(1))) {
+                    break Some(short_id.clone());
+                }
+            }
+            Some(Key::Esc) | None => break None,
+            _ => (),
+        }
+    };
+    thread.clear_status();
+
+    Ok(picked)
+}
+
+/// Edit `text` in the user's editor, handing the terminal over to it while it runs
+fn edit_in_terminal<W: Write>(screen: &mut RawTerminal<W>, text: &str) -> Result<String, Error> {
+    write!(screen, "{}{}", ToMainScreen, cursor::Show)?;
+    screen.flush()?;
+    screen.suspend_raw_mode()?;
+    let edited = editor::edit(text);
+    screen.activate_raw_mode()?;
+    write!(screen, "{}{}", ToAlternateScreen, cursor::Hide)?;
+
+    edited
+}
+
+/// Describe an error from an action taken in the UI, for display in the status line
+fn error_status(action: &str, err: &lobsters::Error) -> String {
+    match err {
+        lobsters::Error::Authorisation => format!("{}: not logged in", action),
+        lobsters::Error::Rejected(message) => format!("{}: {}", action, message),
+        lobsters::Error::Validation(errors) => format!("{}: {}", action, errors.join(", ")),
+        lobsters::Error::RateLimited => format!("{}: too many requests, try again later", action),
+        _ => format!("{}: request failed", action),
+    }
+}
+
 impl Default for Command {
     fn default() -> Self {
         Command::Stories(Stories::default())
@@ -268,9 +1620,3 @@ impl FromStr for UiTheme {
         }
     }
 }
-
-impl Default for UiTheme {
-    fn default() -> Self {
-        UiTheme::Color256
-    }
-}
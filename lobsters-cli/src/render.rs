@@ -1,21 +1,21 @@
 use std::borrow::Cow;
 use std::io::Write;
+use std::ops::Range;
 
-use chrono::prelude::*;
 use chrono_humanize::HumanTime;
+use lobsters::client::REPLIES_PER_PAGE;
 use termion::raw::RawTerminal;
 
 use crate::{
-    app::State,
+    app::{Filters, Inbox, Profile, Reader, Scroll, State, Thread},
     error::Error,
     text::Fancy,
     theme::{Colour, Theme},
     util,
 };
-use lobsters::url::Url;
 
-type Line = Vec<Fancy>;
-type Lines = Vec<Line>;
+pub type Line = Vec<Fancy>;
+pub type Lines = Vec<Line>;
 
 pub fn render_stories(state: &mut State, theme: &Theme, height: usize) -> Result<Lines, Error> {
     let mut lines = Vec::new();
@@ -24,11 +24,6 @@ pub fn render_stories(state: &mut State, theme: &Theme, height: usize) -> Result
     let digits = state.max_score_digits().unwrap_or(1);
 
     for (i, story) in state.stories().iter().enumerate() {
-        // TODO: Map empty strings to None when parsing response
-        let url = match story.url.as_str() {
-            "" => None,
-            url => Some(url.parse::<Url>().map_err(lobsters::Error::from)?),
-        };
         let score = Fancy::new(format!("{:1$}", story.score, digits)).fg(theme.score);
         let title = Fancy::new(format!(" {}", story.title))
             .fg(theme.title)
@@ -36,17 +31,20 @@ pub fn render_stories(state: &mut State, theme: &Theme, height: usize) -> Result
         let tags = story
             .tags
             .iter()
-            .filter_map(|tag| state.get_tag(&tag))
+            .filter_map(|tag| state.get_tag(tag))
             .map(|tag| Fancy::new(format!(" {}", tag.tag)).fg(theme.tag_colour(tag)));
         let domain = Fancy::new(
-            url.and_then(|url| url.domain().map(|d| format!(" {}", d)))
+            story
+                .url
+                .as_ref()
+                .and_then(|url| url.domain().map(|d| format!(" {}", d)))
                 .unwrap_or_else(|| "".to_string()),
         )
         .fg(theme.domain)
         .italic();
 
-        let created_at = story.created_at.parse::<DateTime<FixedOffset>>()?;
-        let meta = format!(
+        let created_at = story.created_at.to_datetime();
+        let mut meta = format!(
             "{:pad$} via {submitter} {when} | {n} comments",
             " ",
             pad = digits,
@@ -54,6 +52,12 @@ pub fn render_stories(state: &mut State, theme: &Theme, height: usize) -> Result
             when = HumanTime::from(created_at),
             n = story.comment_count
         );
+        if state.is_saved(&story.short_id) {
+            meta.push_str(" | saved");
+        }
+        if state.is_hidden(&story.short_id) {
+            meta.push_str(" | hidden");
+        }
 
         let mut line1 = Line::new();
         line1.push(score);
@@ -74,7 +78,343 @@ pub fn render_stories(state: &mut State, theme: &Theme, height: usize) -> Result
         lines.push(line2);
     }
 
-    Ok(limit_lines(state, lines, height))
+    // The last line is reserved for the status line
+    let story_range = state.story_range();
+    let mut lines = limit_lines(
+        state.row_offset_get_mut(),
+        story_range,
+        lines,
+        height.saturating_sub(1),
+    );
+    lines.resize(height.saturating_sub(1), Line::new());
+    let mut status = Line::new();
+    if let Some(title) = state.title() {
+        status.push(Fancy::new(title).fg(theme.byline).bold());
+    }
+    let message = render_status(state.status(), theme);
+    if !status.is_empty() && !message.is_empty() {
+        status.push(Fancy::new(" | ").fg(theme.byline));
+    }
+    status.extend(message);
+    if let Some(count) = state.unread_replies().filter(|&count| count > 0) {
+        let noun = if count == 1 { "reply" } else { "replies" };
+        // Only the first page of replies is counted, a full page means there may be more
+        let more = if count >= REPLIES_PER_PAGE { "+" } else { "" };
+        let separator = if status.is_empty() { "" } else { " | " };
+        status.push(Fancy::new(separator).fg(theme.byline));
+        status.push(
+            Fancy::new(format!("{}{} unread {}", count, more, noun))
+                .fg(theme.score)
+                .bold(),
+        );
+    }
+    lines.push(status);
+
+    Ok(lines)
+}
+
+pub fn render_thread(
+    thread: &mut Thread,
+    theme: &Theme,
+    width: usize,
+    height: usize,
+) -> Result<Lines, Error> {
+    let mut lines = Vec::new();
+    let story = thread.story();
+
+    // Story header
+    let created_at = story.created_at.to_datetime();
+    lines.push(vec![Fancy::new(story.title.as_str())
+        .fg(theme.title)
+        .bold()]);
+    lines.push(vec![Fancy::new(format!(
+        "via {submitter} {when} | {n} comments",
+        submitter = story.submitter_user.username,
+        when = HumanTime::from(created_at),
+        n = story.comment_count
+    ))
+    .fg(theme.byline)]);
+    if let Some(description) = story.description.as_ref().filter(|d| !d.is_empty()) {
+        lines.push(Line::new());
+        for line in util::wrap_html(description, width) {
+            lines.push(vec![Fancy::new(line)]);
+        }
+    }
+    lines.push(Line::new());
+
+    if thread.comments().is_empty() {
+        lines.push(vec![Fancy::new("No comments").fg(theme.byline).italic()]);
+    }
+
+    // Comments, indented by their depth in the thread
+    let mut comment_range = 0..0;
+    for (i, comment) in thread.comments().iter().enumerate() {
+        let indent = " ".repeat(comment.nesting_level().saturating_sub(1) as usize * 2);
+        let created_at = comment.created_at.to_datetime();
+        let byline = format!(
+            "{indent}{score} {user}{hat} {when}",
+            indent = indent,
+            score = comment.score,
+            user = comment.commenting_user.username,
+            hat = comment
+                .hat
+                .as_ref()
+                .map(|hat| format!(" [{}]", hat.hat))
+                .unwrap_or_default(),
+            when = HumanTime::from(created_at),
+        );
+        let text_width = width.saturating_sub(indent.len()).max(1);
+        let text = if comment.is_moderated {
+            vec!["[removed by moderator]".to_string()]
+        } else if comment.is_deleted {
+            vec!["[deleted]".to_string()]
+        } else {
+            util::wrap_html(&comment.comment, text_width)
+        };
+
+        let mut comment_lines = vec![vec![Fancy::new(byline).fg(theme.byline)]];
+        for line in text {
+            comment_lines.push(vec![Fancy::new(format!("{}{}", indent, line))]);
+        }
+
+        if i == thread.current_comment_index() {
+            comment_range = lines.len()..lines.len() + comment_lines.len();
+            comment_lines = comment_lines
+                .into_iter()
+                .map(|line| highlight_line(line, theme.cursor))
+                .collect();
+        }
+
+        lines.extend(comment_lines);
+        lines.push(Line::new());
+    }
+
+    // The last line is reserved for the status line
+    let mut lines = limit_lines(
+        thread.row_offset_get_mut(),
+        comment_range,
+        lines,
+        height.saturating_sub(1),
+    );
+    lines.resize(height.saturating_sub(1), Line::new());
+    lines.push(render_status(thread.status(), theme));
+
+    Ok(lines)
+}
+
+pub fn render_profile(
+    profile: &mut Profile,
+    theme: &Theme,
+    width: usize,
+    height: usize,
+) -> Result<Lines, Error> {
+    let mut lines = Vec::new();
+    let user = profile.user();
+
+    let role = if user.is_admin {
+        " (administrator)"
+    } else if user.is_moderator {
+        " (moderator)"
+    } else {
+        ""
+    };
+    lines.push(vec![
+        Fancy::new(user.username.as_str()).fg(theme.title).bold(),
+        Fancy::new(role).fg(theme.byline),
+    ]);
+
+    let mut meta = Vec::new();
+    if let Some(created_at) = &user.created_at {
+        meta.push(format!(
+            "joined {}",
+            HumanTime::from(created_at.to_datetime())
+        ));
+    }
+    if let Some(karma) = user.karma {
+        meta.push(format!("{} karma", karma));
+    }
+    if let Some(invited_by) = &user.invited_by_user {
+        meta.push(format!("invited by {}", invited_by));
+    }
+    lines.push(vec![Fancy::new(meta.join(" | ")).fg(theme.byline)]);
+
+    let links = [
+        ("homepage", &user.homepage),
+        ("github", &user.github_username),
+        ("mastodon", &user.mastodon_username),
+        ("twitter", &user.twitter_username),
+    ];
+    for (name, link) in links.iter() {
+        if let Some(link) = link {
+            lines.push(vec![
+                Fancy::new(format!("{}: ", name)).fg(theme.byline),
+                Fancy::new(link.as_str()).fg(theme.domain),
+            ]);
+        }
+    }
+
+    if let Some(about) = user.about.as_ref().filter(|about| !about.is_empty()) {
+        lines.push(Line::new());
+        for line in util::wrap_html(about, width) {
+            lines.push(vec![Fancy::new(line)]);
+        }
+    }
+
+    lines.push(Line::new());
+    lines.push(vec![Fancy::new("Recent stories").fg(theme.title).bold()]);
+    if profile.stories().is_empty() {
+        lines.push(vec![Fancy::new("None").fg(theme.byline).italic()]);
+    }
+    let digits = profile
+        .stories()
+        .iter()
+        .map(|story| util::count_digits(story.score))
+        .max()
+        .unwrap_or(1);
+    for story in profile.stories() {
+        lines.push(vec![
+            Fancy::new(format!("{:1$}", story.score, digits)).fg(theme.score),
+            Fancy::new(format!(" {}", story.title)).fg(theme.title),
+        ]);
+    }
+
+    Ok(scroll_lines(lines, profile.as_mut(), height))
+}
+
+pub fn render_inbox(inbox: &mut Inbox, theme: &Theme, height: usize) -> Result<Lines, Error> {
+    let mut lines = Vec::new();
+
+    if inbox.messages().is_empty() {
+        lines.push(vec![Fancy::new("No messages").fg(theme.byline).italic()]);
+    }
+
+    for (i, message) in inbox.messages().iter().enumerate() {
+        let mut subject = Fancy::new(message.subject.as_str()).fg(theme.title);
+        if !message.has_been_read {
+            subject = subject.bold();
+        }
+        let created_at = message.created_at.to_datetime();
+        let byline = format!(
+            "from {author} {when}",
+            author = message.author_username.as_deref().unwrap_or("the system"),
+            when = HumanTime::from(created_at),
+        );
+
+        let mut line1 = vec![subject];
+        let mut line2 = vec![Fancy::new(byline).fg(theme.byline)];
+        if i == inbox.current_message_index() {
+            line1 = highlight_line(line1, theme.cursor);
+            line2 = highlight_line(line2, theme.cursor);
+        }
+
+        lines.push(line1);
+        lines.push(line2);
+    }
+
+    // Each message is two lines, the last line is reserved for the status line
+    let current = inbox.current_message_index() * 2;
+    let mut lines = limit_lines(
+        inbox.row_offset_get_mut(),
+        current..current + 2,
+        lines,
+        height.saturating_sub(1),
+    );
+    lines.resize(height.saturating_sub(1), Line::new());
+    lines.push(render_status(inbox.status(), theme));
+
+    Ok(lines)
+}
+
+pub fn render_filters(filters: &mut Filters, theme: &Theme, height: usize) -> Lines {
+    let width = filters
+        .tags()
+        .iter()
+        .map(|tag| tag.tag.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut lines = Vec::new();
+    for (i, tag) in filters.tags().iter().enumerate() {
+        let checkbox = if filters.is_filtered(tag) {
+            "[x]"
+        } else {
+            "[ ]"
+        };
+        let mut line = vec![
+            Fancy::new(format!("{} ", checkbox)),
+            Fancy::new(format!("{:1$}", tag.tag, width)).fg(theme.tag_colour(tag)),
+        ];
+        if let Some(description) = &tag.description {
+            line.push(Fancy::new(format!("  {}", description)).fg(theme.byline));
+        }
+        if i == filters.current_tag_index() {
+            line = highlight_line(line, theme.cursor);
+        }
+        lines.push(line);
+    }
+
+    // The last line is reserved for the status line
+    let current = filters.current_tag_index();
+    let mut lines = limit_lines(
+        filters.row_offset_get_mut(),
+        current..current + 1,
+        lines,
+        height.saturating_sub(1),
+    );
+    lines.resize(height.saturating_sub(1), Line::new());
+    lines.push(render_status(filters.status(), theme));
+
+    lines
+}
+
+pub fn render_message(
+    reader: &mut Reader,
+    theme: &Theme,
+    width: usize,
+    height: usize,
+) -> Result<Lines, Error> {
+    let mut lines = Vec::new();
+
+    // The subject of the first message, replies only add a Re: to it
+    if let Some(message) = reader.thread().first() {
+        lines.push(vec![Fancy::new(message.subject.as_str())
+            .fg(theme.title)
+            .bold()]);
+    }
+    for message in reader.thread() {
+        let created_at = message.created_at.to_datetime();
+        lines.push(Line::new());
+        lines.push(vec![Fancy::new(format!(
+            "from {author} to {recipient} {when}",
+            author = message.author_username.as_deref().unwrap_or("the system"),
+            recipient = message.recipient_username,
+            when = HumanTime::from(created_at),
+        ))
+        .fg(theme.byline)]);
+        for line in util::wrap(&message.body, width) {
+            lines.push(vec![Fancy::new(line)]);
+        }
+    }
+
+    Ok(scroll_lines(lines, reader.as_mut(), height))
+}
+
+/// The lines visible at the scroll position, which is kept from going past the end of `lines`
+fn scroll_lines(lines: Lines, scroll: &mut Scroll, height: usize) -> Lines {
+    let row_offset = scroll.row_offset_get_mut();
+    *row_offset = (*row_offset).min(lines.len().saturating_sub(height));
+
+    let mut lines: Lines = lines.into_iter().skip(*row_offset).take(height).collect();
+    lines.resize(height, Line::new());
+
+    lines
+}
+
+fn render_status(status: Option<&str>, theme: &Theme) -> Line {
+    match status {
+        Some(status) => vec![Fancy::new(status).fg(theme.byline).italic()],
+        None => Line::new(),
+    }
 }
 
 fn highlight_line(line: Line, colour: Colour) -> Line {
@@ -102,24 +442,27 @@ impl<T> Encompass<T> for std::ops::Range<T> {
     }
 }
 
-fn limit_lines(state: &mut State, lines: Lines, height: usize) -> Lines {
-    // Work out the range of lines to render, ensuring the current story is visible
-    let story_range = state.story_range();
-    let ordering = state.visible_range(height).encompass(&story_range);
-    let row_offset = state.row_offset_get_mut();
+fn limit_lines(
+    row_offset: &mut usize,
+    item_range: Range<usize>,
+    lines: Lines,
+    height: usize,
+) -> Lines {
+    // Work out the range of lines to render, ensuring the current item is visible
+    let visible_range = *row_offset..*row_offset + height;
 
-    match ordering {
-        Some(std::cmp::Ordering::Less) => *row_offset = story_range.start,
+    match visible_range.encompass(&item_range) {
+        Some(std::cmp::Ordering::Less) => *row_offset = item_range.start,
         Some(std::cmp::Ordering::Equal) => (),
-        Some(std::cmp::Ordering::Greater) => *row_offset = story_range.end - height,
+        // Show the start of items that are taller than the screen
+        Some(std::cmp::Ordering::Greater) if item_range.len() > height => {
+            *row_offset = item_range.start
+        }
+        Some(std::cmp::Ordering::Greater) => *row_offset = item_range.end - height,
         None => (),
     }
 
-    lines
-        .into_iter()
-        .skip(*row_offset)
-        .take(height as usize)
-        .collect()
+    lines.into_iter().skip(*row_offset).take(height).collect()
 }
 
 /// Render the lines with offset (x, y)
@@ -1,5 +1,10 @@
+use std::ops::Range;
+
+use lobsters::client::{Feed, TopPeriod};
 use lobsters::url::{self, Url};
 
+use crate::error::ParseFeedError;
+
 pub fn as_usize((x, y): (u16, u16)) -> (usize, usize) {
     (usize::from(x), usize::from(y))
 }
@@ -8,6 +13,20 @@ pub fn parse_url(src: &str) -> Result<Url, url::ParseError> {
     src.parse()
 }
 
+pub fn parse_feed(src: &str) -> Result<Feed, ParseFeedError> {
+    match src {
+        "hottest" => Ok(Feed::Hottest),
+        "newest" => Ok(Feed::Newest),
+        "active" => Ok(Feed::Active),
+        "recent" => Ok(Feed::Recent),
+        "top" | "top/week" => Ok(Feed::Top(TopPeriod::Week)),
+        "top/day" => Ok(Feed::Top(TopPeriod::Day)),
+        "top/month" => Ok(Feed::Top(TopPeriod::Month)),
+        "top/year" => Ok(Feed::Top(TopPeriod::Year)),
+        _ => Err(ParseFeedError(src.to_string())),
+    }
+}
+
 pub fn count_digits(num: i32) -> usize {
     match num {
         0 => 1,
@@ -16,6 +35,167 @@ pub fn count_digits(num: i32) -> usize {
     }
 }
 
+/// Convert the HTML of a comment or story text into plain text
+///
+/// Block elements are separated by blank lines, other tags are removed.
+pub fn html_to_text(html: &str) -> String {
+    html_to_lines(html)
+        .into_iter()
+        .map(|(line, _preformatted)| line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Convert HTML into plain text like `html_to_text`, wrapping it into lines of at most `width`
+/// columns
+///
+/// Lines from `pre` blocks are kept as they are, as wrapping would break their layout.
+pub fn wrap_html(html: &str, width: usize) -> Vec<String> {
+    let mut wrapped = Vec::new();
+    for (line, preformatted) in html_to_lines(html) {
+        if preformatted {
+            wrapped.push(line);
+        } else {
+            wrap_line(&line, width, &mut wrapped);
+        }
+    }
+    wrapped
+}
+
+/// The lines of the plain text version of `html`, and whether each is preformatted
+fn html_to_lines(html: &str) -> Vec<(String, bool)> {
+    let mut text = String::with_capacity(html.len());
+    let mut chars = html.chars();
+    // The lines of `text` that are preformatted, and the first line of the current `pre` block
+    let mut pre_lines: Vec<Range<usize>> = Vec::new();
+    let mut pre_start = None;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '<' => {
+                let tag: String = chars.by_ref().take_while(|&c| c != '>').collect();
+                let name = tag
+                    .trim_start_matches('/')
+                    .split(|c: char| c.is_whitespace() || c == '/')
+                    .next()
+                    .unwrap_or("")
+                    .to_lowercase();
+                let closing = tag.starts_with('/');
+
+                match name.as_str() {
+                    "p" | "blockquote" | "ul" | "ol" if closing => text.push_str("\n\n"),
+                    "pre" if closing => {
+                        if let Some(start) = pre_start.take() {
+                            pre_lines.push(start..line_count(&text) + 1);
+                        }
+                        text.push_str("\n\n");
+                    }
+                    "pre" => {
+                        text.push_str("\n\n");
+                        pre_start = Some(line_count(&text));
+                    }
+                    "br" => text.push('\n'),
+                    "li" if !closing => text.push_str("\n• "),
+                    _ => (),
+                }
+            }
+            '&' => {
+                let entity: String = chars.by_ref().take_while(|&c| c != ';').collect();
+                match entity.as_str() {
+                    "amp" => text.push('&'),
+                    "lt" => text.push('<'),
+                    "gt" => text.push('>'),
+                    "quot" => text.push('"'),
+                    "#39" | "#x27" | "apos" => text.push('\''),
+                    "nbsp" => text.push(' '),
+                    _ => {
+                        text.push('&');
+                        text.push_str(&entity);
+                        text.push(';');
+                    }
+                }
+            }
+            '\n' if pre_start.is_none() => text.push(' '),
+            c => text.push(c),
+        }
+    }
+    if let Some(start) = pre_start {
+        pre_lines.push(start..line_count(&text) + 1);
+    }
+
+    // Trim each line and collapse runs of blank lines
+    let mut lines: Vec<(String, bool)> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        let preformatted = pre_lines.iter().any(|lines| lines.contains(&index));
+        let line = if preformatted {
+            line
+        } else {
+            line.trim_start()
+        };
+        if line.is_empty() && lines.last().is_none_or(|(last, _)| last.is_empty()) {
+            continue;
+        }
+        lines.push((line.to_string(), preformatted));
+    }
+    while lines.last().is_some_and(|(last, _)| last.is_empty()) {
+        lines.pop();
+    }
+
+    lines
+}
+
+/// The number of complete lines in `text`, which is the index of the line being written
+fn line_count(text: &str) -> usize {
+    text.matches('\n').count()
+}
+
+/// Wrap text into lines of at most `width` columns, breaking on spaces
+///
+/// Words wider than `width` are placed on a line of their own. The indentation of a line is
+/// kept, and repeated on the lines it wraps onto, and runs of spaces within a line are kept
+/// unless the line breaks there.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut wrapped = Vec::new();
+    for line in text.lines() {
+        wrap_line(line, width, &mut wrapped);
+    }
+    wrapped
+}
+
+fn wrap_line(line: &str, width: usize, wrapped: &mut Vec<String>) {
+    let words = line.trim_start_matches(' ');
+    let indent = &line[..line.len() - words.len()];
+    // Lines indented by most of the width wrap without their indentation
+    let indent = if indent.len() < width / 2 { indent } else { "" };
+
+    let mut current = indent.to_string();
+    let mut cols = indent.len();
+    let mut spaces = 0;
+    for (i, word) in words.split(' ').enumerate() {
+        if i > 0 {
+            spaces += 1;
+        }
+        if word.is_empty() {
+            continue;
+        }
+
+        let word_cols = wcwidth::str_width(word).unwrap_or(0);
+        if cols > indent.len() && cols + spaces + word_cols > width {
+            wrapped.push(std::mem::replace(&mut current, indent.to_string()));
+            cols = indent.len();
+        } else {
+            current.extend(std::iter::repeat_n(' ', spaces));
+            cols += spaces;
+        }
+        current.push_str(word);
+        cols += word_cols;
+        spaces = 0;
+    }
+
+    wrapped.push(current);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -34,4 +214,65 @@ mod tests {
         assert_eq!(count_digits(-10), 3);
         assert_eq!(count_digits(-1), 2);
     }
+
+    #[test]
+    fn test_parse_feed() {
+        assert_eq!(parse_feed("newest").unwrap(), Feed::Newest);
+        assert_eq!(parse_feed("top").unwrap(), Feed::Top(TopPeriod::Week));
+        assert_eq!(parse_feed("top/year").unwrap(), Feed::Top(TopPeriod::Year));
+        assert!(parse_feed("top/decade").is_err());
+    }
+
+    #[test]
+    fn test_html_to_text() {
+        let html = "<p>First <em>paragraph</em> &amp; more\ntext</p>\n<p>Second</p>\n\
+                    <ul>\n<li>one</li>\n<li>two</li>\n</ul>\n";
+        assert_eq!(
+            html_to_text(html),
+            "First paragraph & more text\n\nSecond\n\n• one\n• two"
+        );
+    }
+
+    #[test]
+    fn test_html_to_text_pre() {
+        let html = "<p>Try this:</p>\n<pre><code>fn main() {\n    println!(\"hi\");\n}\n\
+                    </code></pre>\n<p>  Then   run it.</p>\n";
+        assert_eq!(
+            html_to_text(html),
+            "Try this:\n\nfn main() {\n    println!(\"hi\");\n}\n\nThen   run it."
+        );
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(
+            wrap("the quick brown fox\n\njumps", 10),
+            vec!["the quick", "brown fox", "", "jumps"]
+        );
+        assert_eq!(
+            wrap("supercalifragilistic word", 5),
+            vec!["supercalifragilistic", "word"]
+        );
+        assert_eq!(
+            wrap("    let x  = 1; // one two", 16),
+            vec!["    let x  = 1;", "    // one two"]
+        );
+    }
+
+    #[test]
+    fn test_wrap_html_pre() {
+        let html = "<p>Try this program out:</p>\n<pre><code>fn main() {\n    \
+                    println!(\"hello world\");\n}\n</code></pre>\n";
+        assert_eq!(
+            wrap_html(html, 12),
+            vec![
+                "Try this",
+                "program out:",
+                "",
+                "fn main() {",
+                "    println!(\"hello world\");",
+                "}",
+            ]
+        );
+    }
 }
@@ -1,78 +1,228 @@
 //! Lobsters client
 
+mod builder;
 mod http_client;
+mod scrape;
+mod session;
+pub mod transport;
 
+use std::collections::HashSet;
 use std::fs::{self, DirBuilder, File};
 use std::io;
 use std::io::BufReader;
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
 
 use cookie_store::CookieStore;
 use directories::ProjectDirs;
-use futures::{Future, IntoFuture, Stream};
+use futures::future::Either;
+use futures::{stream, Future, IntoFuture, Stream};
 use kuchiki::traits::TendrilSink;
 use log::debug;
-use reqwest::header::LOCATION;
-use reqwest::r#async::{ClientBuilder, Response};
-use reqwest::RedirectPolicy;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use url::Url;
 
 use crate::error::Error;
-use crate::models::{NewComment, Story, StoryId, Tag};
+use crate::models::{
+    Comment, CommentFlagReason, CommentId, Hat, Invitation, InvitationTree, Message, MessageId,
+    Moderation, ModerationTarget, NewComment, NewInvitation, NewMessage, NewStory, Reply, ShortTag,
+    Story, StoryFlagReason, StoryId, Tag, User,
+};
 
-use http_client::HttpClient;
+pub use builder::ClientBuilder;
+use session::Session;
+use transport::{Response, StatusCode};
 
 /// The main Lobsters client
+#[derive(Clone)]
 pub struct Client {
-    http: HttpClient,
+    http: Session,
+    cookie_store_path: Option<PathBuf>,
+}
+
+/// An error reported in a JSON response
+#[derive(Deserialize)]
+struct ErrorMessage {
+    error: String,
 }
 
 /// Respresent a page number for a request greater that 1
 pub struct Page(u32);
 
+/// A listing of stories on the site
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feed {
+    /// The front page, ranked by hotness
+    Hottest,
+    /// Most recently submitted stories, newest first
+    Newest,
+    /// Stories with the most recent comments
+    Active,
+    /// Recently submitted stories that have not yet reached the front page
+    Recent,
+    /// Highest scoring stories submitted within a period
+    Top(TopPeriod),
+}
+
+/// The period covered by `Feed::Top`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopPeriod {
+    /// The last day
+    Day,
+    /// The last week
+    Week,
+    /// The last month
+    Month,
+    /// The last year
+    Year,
+}
+
+/// The order of search results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchOrder {
+    /// Best matches first
+    Relevance,
+    /// Most recent first
+    Newest,
+    /// Highest scoring first
+    Points,
+}
+
+/// Which replies to the logged in user to retrieve
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyFilter {
+    /// Replies to comments and stories
+    All,
+    /// Replies to comments
+    Comments,
+    /// Replies to stories
+    Stories,
+    /// Replies that have not been read
+    Unread,
+}
+
+/// Which entries of the moderation log to retrieve
+#[derive(Debug, Clone, Default)]
+pub struct ModerationFilter {
+    /// Only include actions taken by this moderator
+    pub moderator: Option<String>,
+    /// Only include actions on these kinds of thing, all kinds are included if empty
+    pub targets: Vec<ModerationTarget>,
+}
+
+/// The result of a successful login request
+#[derive(Debug)]
+pub enum LoginState {
+    /// The client is now logged in
+    LoggedIn,
+    /// The account has two-factor authentication enabled and a TOTP code is required to
+    /// complete the login
+    TwoFactorRequired(TwoFactorChallenge),
+}
+
+/// A pending two-factor authentication challenge, complete it with `Client::login_2fa`
+#[derive(Debug)]
+pub struct TwoFactorChallenge {
+    csrf_token: String,
+}
+
+const TWOFA_PATH: &str = "login/2fa";
+
+/// Form parameters for POST requests that don't need any
+const NO_PARAMS: [(&str, &str); 0] = [];
+
+/// The name of the profile used when one is not specified
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// The number of replies the site lists on each page of `Client::replies`
+pub const REPLIES_PER_PAGE: usize = 25;
+
 fn config_path() -> Result<PathBuf, Error> {
     ProjectDirs::from("rs", "lobste", env!("CARGO_PKG_NAME"))
         .map(|proj_dirs| proj_dirs.config_dir().to_path_buf())
         .ok_or_else(|| Error::HomeNotFound)
 }
 
-fn cookie_store_path() -> Result<PathBuf, Error> {
+/// Path of the cookie store used before stores were kept per site and profile
+fn legacy_cookie_store_path() -> Result<PathBuf, Error> {
     let mut cookie_store_path = config_path()?;
     cookie_store_path.push("cookies.json");
     Ok(cookie_store_path)
 }
 
+/// Path of the cookie store for `profile` on the site at `base_url`
+///
+/// Stores are laid out as `cookies/<host>/<profile>.json` in the config directory. The port is
+/// appended to the host if it is not the default for the scheme.
+fn cookie_store_path(base_url: &Url, profile: &str) -> Result<PathBuf, Error> {
+    let host = base_url
+        .host_str()
+        .ok_or_else(|| Error::Url(url::ParseError::EmptyHost))?;
+    let site = match base_url.port() {
+        Some(port) => format!("{}_{}", host, port),
+        None => host.to_string(),
+    };
+    if !is_valid_profile(profile) {
+        return Err(Error::InvalidProfile(profile.to_string()));
+    }
+
+    let mut cookie_store_path = config_path()?;
+    cookie_store_path.push("cookies");
+    cookie_store_path.push(site);
+    cookie_store_path.push(format!("{}.json", profile));
+    Ok(cookie_store_path)
+}
+
+/// Profile names become file names so they are limited to a safe set of characters
+fn is_valid_profile(profile: &str) -> bool {
+    !profile.is_empty()
+        && !profile.starts_with('.')
+        && profile
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// Browsers submit textareas with CRLF line endings, so the site may store either
+fn normalise_newlines(text: &str) -> String {
+    text.replace("\r\n", "\n").trim_end().to_string()
+}
+
+fn load_cookies(path: &Path) -> Result<CookieStore, Error> {
+    if path.exists() {
+        let cookie_file = BufReader::new(File::open(path)?);
+        CookieStore::load_json(cookie_file).map_err(|_err| Error::CookieStore)
+    } else {
+        Ok(CookieStore::default())
+    }
+}
+
 impl Client {
     /// Create a new client
     ///
-    /// Will attempt to load the cookie store if it exists.
+    /// Will attempt to load the cookie store for the default profile if it exists. Use
+    /// `ClientBuilder` to select a different profile or cookie store.
     pub fn new(base_url: Url) -> Result<Self, Error> {
-        let cookie_store_path = cookie_store_path()?;
-
-        let cookies = if cookie_store_path.exists() {
-            let cookie_file = BufReader::new(File::open(cookie_store_path)?);
-            CookieStore::load_json(cookie_file).map_err(|_err| Error::CookieStore)?
-        } else {
-            CookieStore::default()
-        };
-
-        let client = ClientBuilder::new()
-            .redirect(RedirectPolicy::none())
-            .use_rustls_tls()
-            .build()?;
-        let http = HttpClient::new(base_url, client, Arc::new(Mutex::new(cookies)));
+        ClientBuilder::new(base_url).build()
+    }
 
-        Ok(Client { http })
+    /// Create a `ClientBuilder` to configure a `Client`
+    pub fn builder(base_url: Url) -> ClientBuilder {
+        ClientBuilder::new(base_url)
     }
 
     /// Attempt to authenticate with the server
+    ///
+    /// If the account has two-factor authentication enabled the returned `LoginState` will
+    /// contain a `TwoFactorChallenge` that must be completed with `login_2fa`.
     pub fn login(
         &self,
         username_or_email: String,
         password: String,
-    ) -> impl Future<Item = (), Error = Error> {
-        let get_token = self.http.get("login").and_then(Self::extract_csrf_token);
+    ) -> impl Future<Item = LoginState, Error = Error> {
+        let get_token = self
+            .http
+            .get("login")
+            .and_then(|res| Self::extract_csrf_token(&res));
 
         // https://github.com/lobsters/lobsters/blob/9711868670e9c638a55fc94ab8ae48002d31ad06/app/controllers/login_controller.rb#L70
         let success_url = self.http.base_url().join("lobsters-login-success");
@@ -84,6 +234,7 @@ impl Client {
         };
 
         let client = self.http.clone();
+        let twofa_url = self.http.base_url().join(TWOFA_PATH).ok();
         let login = move |(success_url, token): (Url, _)| {
             let params = [
                 ("email", username_or_email),
@@ -91,51 +242,260 @@ impl Client {
                 ("referer", success_url.to_string()),
             ];
 
-            client
-                .post("login", params, token)
-                .and_then(|res| {
-                    let location = if res.status().is_redirection() {
-                        res.headers()
-                            .get(LOCATION)
-                            .and_then(|header| header.to_str().ok())
-                            .map(std::string::ToString::to_string)
-                    } else {
-                        None
-                    };
-                    res.into_body()
-                        .concat2()
-                        .map_err(Error::from)
-                        .map(|body| (location, body))
-                })
-                .and_then(|(location, body)| {
-                    let b = std::str::from_utf8(&body).unwrap();
-                    debug!("login body = {}", b);
-
-                    // Success is deemed to be if the response redirects to the success_url
-                    if location
-                        .and_then(|url| url.parse().ok())
-                        .map(move |url: Url| url == success_url)
-                        .unwrap_or(false)
-                    {
-                        futures::future::ok(())
-                    } else {
-                        futures::future::err(Error::Authorisation)
+            client.post("login", params, token).and_then(move |res| {
+                debug!("login body = {}", res.text().unwrap_or_default());
+
+                // Success is deemed to be if the response redirects to the success_url,
+                // accounts with 2FA enabled are redirected to the 2FA page instead
+                match res.location() {
+                    Some(ref url) if *url == success_url => {
+                        Either::A(futures::future::ok(LoginState::LoggedIn))
                     }
-                })
+                    Some(ref url) if Some(url) == twofa_url.as_ref() => {
+                        let challenge = client
+                            .get(TWOFA_PATH)
+                            .and_then(|res| Self::extract_csrf_token(&res))
+                            .map(|csrf_token| {
+                                LoginState::TwoFactorRequired(TwoFactorChallenge { csrf_token })
+                            });
+                        Either::B(challenge)
+                    }
+                    _ => Either::A(futures::future::err(Error::Authorisation)),
+                }
+            })
         };
 
         get_token.and_then(success_url).and_then(login)
     }
 
+    /// Complete a two-factor authentication challenge with a TOTP code
+    pub fn login_2fa(
+        &self,
+        challenge: TwoFactorChallenge,
+        totp_code: String,
+    ) -> impl Future<Item = (), Error = Error> {
+        let params = [("totp_code", totp_code)];
+
+        self.http
+            .post("login/2fa_verify", params, challenge.csrf_token)
+            .and_then(move |res| {
+                debug!("2fa body = {}", res.text().unwrap_or_default());
+
+                // An incorrect code redirects back to the 2FA page and an expired challenge to
+                // the login page, success redirects elsewhere
+                match res.location() {
+                    Some(ref location)
+                        if location.path() != "/login"
+                            && location.path() != format!("/{}", TWOFA_PATH) =>
+                    {
+                        Ok(())
+                    }
+                    _ => Err(Error::Authorisation),
+                }
+            })
+    }
+
+    /// Log out of the current session and clear the cookie store
+    ///
+    /// The cookie store is left as is if the site doesn't confirm the logout. Call
+    /// `save_cookies` afterwards to also clear the persisted session.
+    pub fn logout(&self) -> impl Future<Item = (), Error = Error> {
+        let client = self.http.clone();
+        let logout = move |token| {
+            client
+                .post("logout", NO_PARAMS, token)
+                .and_then(|res| Self::check_form_response(&res))
+                .map(move |()| client.clear_cookies())
+        };
+
+        self.csrf_token().and_then(logout)
+    }
+
+    /// Determine the username of the logged in user, if any
+    pub fn whoami(&self) -> impl Future<Item = Option<String>, Error = Error> {
+        self.http
+            .get("about")
+            .and_then(|res| res.text().map(Self::extract_username_from_html))
+    }
+
     /// Retrieve the front page stories, newest first
     pub fn index(&self, page: Option<Page>) -> impl Future<Item = Vec<Story>, Error = Error> {
-        let path = page
-            .map(|Page(page): Page| format!("page/{}", page))
-            .unwrap_or_else(|| "".to_string());
+        self.stories(Feed::Hottest, page)
+    }
 
-        self.http
-            .get_json(&path)
-            .and_then(|mut res| res.json::<Vec<Story>>().map_err(Error::from))
+    /// Retrieve the stories of a feed
+    pub fn stories(
+        &self,
+        feed: Feed,
+        page: Option<Page>,
+    ) -> impl Future<Item = Vec<Story>, Error = Error> {
+        self.listing(feed.path(), page)
+    }
+
+    /// Retrieve the stories of a feed as a stream, fetching successive pages as it's consumed
+    ///
+    /// The stream ends at the first empty page. Up to `concurrency` pages are fetched at once.
+    /// Stories that move onto a later page while paging are only included once. Use
+    /// `Stream::take` to limit the number of stories.
+    pub fn story_stream(
+        &self,
+        feed: Feed,
+        concurrency: usize,
+    ) -> impl Stream<Item = Story, Error = Error> {
+        let client = self.clone();
+        Self::paged_stories(move |page| client.stories(feed, page), concurrency)
+    }
+
+    /// Retrieve all of the stories saved by the logged in user as a stream
+    ///
+    /// Pages are fetched as by `story_stream`.
+    pub fn saved_story_stream(
+        &self,
+        concurrency: usize,
+    ) -> impl Stream<Item = Story, Error = Error> {
+        let client = self.clone();
+        Self::paged_stories(move |page| client.saved_stories(page), concurrency)
+    }
+
+    /// Retrieve the stories tagged with any of `tags`
+    ///
+    /// Returns `Error::NoTags` if `tags` is empty.
+    pub fn tagged_stories(
+        &self,
+        tags: &[ShortTag],
+        page: Option<Page>,
+    ) -> impl Future<Item = Vec<Story>, Error = Error> {
+        if tags.is_empty() {
+            return Either::A(futures::future::err(Error::NoTags));
+        }
+
+        let tags = tags
+            .iter()
+            .map(|tag| tag.0.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        Either::B(self.listing(format!("t/{}", tags), page))
+    }
+
+    /// Retrieve the stories linking to `domain`
+    pub fn domain_stories(
+        &self,
+        domain: &str,
+        page: Option<Page>,
+    ) -> impl Future<Item = Vec<Story>, Error = Error> {
+        self.listing(format!("domain/{}", domain), page)
+    }
+
+    /// Retrieve the profile of a user
+    ///
+    /// Returns `Error::NotFound` if there is no user named `username`.
+    pub fn user(&self, username: &str) -> impl Future<Item = User, Error = Error> {
+        let path = format!("u/{}.json", username);
+
+        self.http.get_json(&path).and_then(|res| {
+            if res.status == StatusCode::NOT_FOUND {
+                Err(Error::NotFound)
+            } else {
+                res.json::<User>()
+            }
+        })
+    }
+
+    /// Retrieve the stories submitted by a user, newest first
+    pub fn user_stories(
+        &self,
+        username: &str,
+        page: Option<Page>,
+    ) -> impl Future<Item = Vec<Story>, Error = Error> {
+        self.listing(format!("newest/{}", username), page)
+    }
+
+    /// Retrieve the comment threads a user has participated in, newest first
+    ///
+    /// Each thread is flattened into its comments, `Comment::nesting_level` gives the depth of
+    /// each comment within its thread.
+    pub fn user_threads(
+        &self,
+        username: &str,
+        page: Option<Page>,
+    ) -> impl Future<Item = Vec<Comment>, Error = Error> {
+        self.listing(format!("threads/{}", username), page)
+    }
+
+    /// Retrieve the newest comments across all stories, newest first
+    ///
+    /// `Comment::story_id` and `Comment::story_title` are filled in on the returned comments.
+    /// The titles are only present in the HTML of the page, so it is fetched as well as the JSON.
+    pub fn recent_comments(
+        &self,
+        page: Option<Page>,
+    ) -> impl Future<Item = Vec<Comment>, Error = Error> {
+        let path = Self::paginated_path("comments".to_string(), page);
+        let http = self.http.clone();
+
+        let comments = self.http.get_json(&path).and_then(|res| {
+            let mut comments = res.json::<Vec<Comment>>()?;
+            for comment in &mut comments {
+                comment.story_id = Url::parse(&comment.url)
+                    .ok()
+                    .and_then(|url| Self::story_id_from_url(&url));
+            }
+            Ok(comments)
+        });
+
+        // The pages are requested one after the other, which keeps the order of requests fixed
+        comments.and_then(move |mut comments| {
+            http.get(&path).and_then(move |res| {
+                let titles = scrape::story_titles_from_html(res.text()?);
+                for comment in &mut comments {
+                    comment.story_title = comment
+                        .story_id
+                        .as_ref()
+                        .and_then(|story_id| titles.get(story_id).cloned());
+                }
+                Ok(comments)
+            })
+        })
+    }
+
+    /// Search for stories matching `query`
+    ///
+    /// The results are parsed from HTML, which lacks some of the information in the JSON API.
+    /// Missing fields are left empty.
+    pub fn search_stories(
+        &self,
+        query: &str,
+        order: SearchOrder,
+        page: Option<Page>,
+    ) -> impl Future<Item = Vec<Story>, Error = Error> {
+        let base_url = self.base_url().clone();
+
+        self.search(query, "stories", order, page)
+            .and_then(move |res| {
+                res.text()
+                    .map(|body| scrape::stories_from_html(&base_url, body))
+            })
+    }
+
+    /// Search for comments matching `query`
+    ///
+    /// The results are parsed from HTML, which lacks some of the information in the JSON API.
+    /// Missing fields are left empty, `Comment::story_id` and `Comment::story_title` are filled
+    /// in.
+    pub fn search_comments(
+        &self,
+        query: &str,
+        order: SearchOrder,
+        page: Option<Page>,
+    ) -> impl Future<Item = Vec<Comment>, Error = Error> {
+        let base_url = self.base_url().clone();
+
+        self.search(query, "comments", order, page)
+            .and_then(move |res| {
+                res.text()
+                    .map(|body| scrape::comments_from_html(&base_url, body))
+            })
     }
 
     /// Retrieve the comments for a story
@@ -144,64 +504,449 @@ impl Client {
 
         self.http
             .get_json(&path)
-            .and_then(|mut res| res.json::<Story>().map_err(Error::from))
+            .and_then(|res| res.json::<Story>())
     }
 
     /// Retrieve the list of tags on the site
     pub fn tags(&self) -> impl Future<Item = Vec<Tag>, Error = Error> {
         self.http
             .get_json("tags")
-            .and_then(|mut res| res.json::<Vec<Tag>>().map_err(Error::from))
+            .and_then(|res| res.json::<Vec<Tag>>())
     }
 
-    /// Post a new comment on a story
-    pub fn post_comment(
-        &self,
-        comment: NewComment,
-    ) -> impl Future<Item = Option<String>, Error = Error> {
-        // Need to fetch a page to get a CSRF token, /about seems like one of the cheapest
-        // pages to fetch
-        let get_token = self.http.get("about").and_then(Self::extract_csrf_token);
+    /// Retrieve the tags filtered out of the front page and other listings
+    ///
+    /// The filters of users that aren't logged in are kept in a cookie by the site, so they are
+    /// retained in the cookie store instead of the account.
+    pub fn filtered_tags(&self) -> impl Future<Item = Vec<ShortTag>, Error = Error> {
+        self.http
+            .get("filters")
+            .and_then(|res| res.text().and_then(scrape::filtered_tags_from_html))
+    }
 
-        let client = self.http.clone();
-        let comment = move |token| {
-            client
-                .post("comments", comment, token)
+    /// Replace the tags filtered out of the front page and other listings with `tags`
+    pub fn set_filtered_tags(&self, tags: &[ShortTag]) -> impl Future<Item = (), Error = Error> {
+        let params: Vec<_> = tags.iter().map(|tag| ("tags[]", tag.0.clone())).collect();
+        self.form_action("filters".to_string(), params)
+    }
+
+    /// Invite someone to join the site by email
+    ///
+    /// The site reports problems with the invitation, like an invalid email address, on the
+    /// page it redirects to. These are returned as `Error::Validation`.
+    pub fn send_invitation(
+        &self,
+        invitation: NewInvitation,
+    ) -> impl Future<Item = (), Error = Error> {
+        let http = self.http.clone();
+        let post = move |token| {
+            let follow = http.clone();
+            http.post("invitations", invitation, token)
+                .and_then(|res| Self::check_form_response(&res).map(|()| res.location()))
+                .and_then(move |location| {
+                    let path = location
+                        .map(|location| location.path().trim_start_matches('/').to_string())
+                        .unwrap_or_default();
+                    follow.get(&path)
+                })
                 .and_then(|res| {
-                    let location = res
-                        .headers()
-                        .get(LOCATION)
-                        .and_then(|header| header.to_str().ok())
-                        .map(std::string::ToString::to_string);
-                    res.into_body()
-                        .concat2()
-                        .map_err(Error::from)
-                        .map(|body| (location, body))
+                    let errors = Self::extract_form_errors_from_html(res.text()?);
+                    if errors.is_empty() {
+                        Ok(())
+                    } else {
+                        Err(Error::Validation(errors))
+                    }
                 })
-                .and_then(|(location, body)| {
-                    let b = std::str::from_utf8(&body).unwrap();
-                    debug!("body = {}", b);
+        };
 
-                    // TODO: Determine success
+        self.csrf_token().and_then(post)
+    }
 
-                    futures::future::ok(location)
-                })
+    /// Retrieve the invitations sent by the logged in user that haven't been accepted yet
+    pub fn invitations(&self) -> impl Future<Item = Vec<Invitation>, Error = Error> {
+        self.http.get("invitations").and_then(|res| {
+            if res.location().is_some() {
+                Err(Error::Authorisation)
+            } else {
+                res.text().map(scrape::invitations_from_html)
+            }
+        })
+    }
+
+    /// Retrieve the users invited by a user, and the users they invited in turn
+    ///
+    /// This is taken from the tree of all users on the site, which is a large page.
+    pub fn invitation_tree(
+        &self,
+        username: &str,
+    ) -> impl Future<Item = InvitationTree, Error = Error> {
+        let username = username.to_string();
+        self.http.get("users").and_then(move |res| {
+            let body = res.text()?;
+            scrape::invitation_tree_from_html(body, &username).ok_or(Error::NotFound)
+        })
+    }
+
+    /// Retrieve a page of the moderation log, newest first
+    ///
+    /// The log is parsed from HTML as there is no JSON version of it.
+    pub fn moderations(
+        &self,
+        filter: &ModerationFilter,
+        page: Option<Page>,
+    ) -> impl Future<Item = Vec<Moderation>, Error = Error> {
+        let base_url = self.base_url().clone();
+        let mut params = url::form_urlencoded::Serializer::new(String::new());
+        if let Some(moderator) = &filter.moderator {
+            params.append_pair("moderator", moderator);
+        }
+        for target in &filter.targets {
+            let what = match target {
+                ModerationTarget::Story => "stories",
+                ModerationTarget::Comment => "comments",
+                ModerationTarget::Tag => "tags",
+                ModerationTarget::User => "users",
+            };
+            params.append_pair(&format!("what[{}]", what), what);
+        }
+        if let Some(Page(page)) = page {
+            params.append_pair("page", &page.to_string());
+        }
+
+        let params = params.finish();
+        let path = if params.is_empty() {
+            "moderations".to_string()
+        } else {
+            format!("moderations?{}", params)
+        };
+        self.http.get(&path).and_then(move |res| {
+            if !res.status.is_success() || res.location().is_some() {
+                return Err(Self::response_error(&res));
+            }
+            res.text()
+                .map(|body| scrape::moderations_from_html(&base_url, body))
+        })
+    }
+
+    /// Retrieve all of the hats on the site, along with the users they were granted to
+    pub fn hats(&self) -> impl Future<Item = Vec<Hat>, Error = Error> {
+        self.http
+            .get("hats")
+            .and_then(|res| res.text().map(scrape::hats_from_html))
+    }
+
+    /// Retrieve the hats the logged in user can wear
+    ///
+    /// The `short_id` of these hats can be used as the `hat_id` of a `NewComment` or
+    /// `NewMessage`.
+    pub fn wearable_hats(&self) -> impl Future<Item = Vec<Hat>, Error = Error> {
+        // The message form offers the hats of the user, it's the only page that always has it
+        self.http.get("messages").and_then(|res| {
+            if res.location().is_some() {
+                Err(Error::Authorisation)
+            } else {
+                res.text().map(scrape::wearable_hats_from_html)
+            }
+        })
+    }
+
+    /// Post a new comment on a story
+    ///
+    /// Returns the newly created comment. If the site rejects the comment `Error::Validation` is
+    /// returned with the problems it reported.
+    pub fn post_comment(&self, comment: NewComment) -> impl Future<Item = Comment, Error = Error> {
+        let http = self.http.clone();
+        let client = self.clone();
+        let post = move |token| {
+            http.post("comments", comment, token)
+                .and_then(move |res| client.created_comment(&res))
+        };
+
+        self.csrf_token().and_then(post)
+    }
+
+    /// Retrieve the source text of a comment for editing
+    ///
+    /// Only comments that the logged in user is allowed to edit can be retrieved.
+    pub fn comment_source(
+        &self,
+        comment_id: &CommentId,
+    ) -> impl Future<Item = String, Error = Error> {
+        self.http
+            .get(&format!("comments/{}/edit", comment_id.0))
+            .and_then(|res| {
+                if res.status.is_success() && res.location().is_none() {
+                    res.text().and_then(Self::extract_comment_source_from_html)
+                } else {
+                    Err(Self::response_error(&res))
+                }
+            })
+    }
+
+    /// Replace the text of a comment
+    ///
+    /// The site answers the same way whether or not it saved the comment, so the comment is
+    /// fetched again to confirm the change. If the site rejects the comment `Error::Validation`
+    /// is returned with the problems it reported.
+    pub fn update_comment(
+        &self,
+        comment_id: &CommentId,
+        comment: String,
+    ) -> impl Future<Item = (), Error = Error> {
+        let path = format!("comments/{}", comment_id.0);
+        let comment_id = comment_id.clone();
+        let http = self.http.clone();
+        let client = self.clone();
+        let update = move |token| {
+            let expected = normalise_newlines(&comment);
+            // Rails routes a POST with _method=patch to the update action
+            let params = [("_method", "patch".to_string()), ("comment", comment)];
+            http.post(&path, params, token).and_then(move |res| {
+                if !res.status.is_success() || res.location().is_some() {
+                    return Either::A(futures::future::err(Self::response_error(&res)));
+                }
+
+                let confirm = client.comment_source(&comment_id).and_then(move |source| {
+                    if normalise_newlines(&source) == expected {
+                        Ok(())
+                    } else {
+                        Err(Self::response_error(&res))
+                    }
+                });
+                Either::B(confirm)
+            })
+        };
+
+        self.csrf_token().and_then(update)
+    }
+
+    /// Delete a comment
+    pub fn delete_comment(&self, comment_id: &CommentId) -> impl Future<Item = (), Error = Error> {
+        self.action(format!("comments/{}/delete", comment_id.0), NO_PARAMS)
+    }
+
+    /// Restore a deleted comment
+    pub fn undelete_comment(
+        &self,
+        comment_id: &CommentId,
+    ) -> impl Future<Item = (), Error = Error> {
+        self.action(format!("comments/{}/undelete", comment_id.0), NO_PARAMS)
+    }
+
+    /// Submit a new story
+    ///
+    /// If the site rejects the story `Error::Validation` is returned with the problems it reported,
+    /// such as a URL that was already submitted or an unknown tag.
+    pub fn submit_story(&self, story: NewStory) -> impl Future<Item = Story, Error = Error> {
+        let mut params = vec![
+            ("story[title]", story.title),
+            ("story[url]", story.url.unwrap_or_default()),
+            ("story[description]", story.description.unwrap_or_default()),
+            (
+                "story[user_is_author]",
+                if story.user_is_author { "1" } else { "0" }.to_string(),
+            ),
+        ];
+        params.extend(
+            story
+                .tags
+                .into_iter()
+                .map(|ShortTag(tag)| ("story[tags_a][]", tag)),
+        );
+
+        let http = self.http.clone();
+        let client = self.clone();
+        let submit = move |token| {
+            http.post("stories", params, token).and_then(move |res| {
+                // Success redirects to the new story, failure renders the form with errors
+                match res.location().as_ref().and_then(Self::story_id_from_url) {
+                    Some(story_id) => Either::A(client.story(&story_id)),
+                    None => Either::B(futures::future::err(Self::response_error(&res))),
+                }
+            })
         };
 
-        get_token.and_then(comment)
+        self.csrf_token().and_then(submit)
+    }
+
+    /// Retrieve the messages in the inbox of the logged in user, newest first
+    pub fn inbox(&self) -> impl Future<Item = Vec<Message>, Error = Error> {
+        self.listing("messages".to_string(), None)
+    }
+
+    /// Retrieve the messages sent by the logged in user, newest first
+    pub fn sent_messages(&self) -> impl Future<Item = Vec<Message>, Error = Error> {
+        self.listing("messages/sent".to_string(), None)
+    }
+
+    /// Retrieve the thread of a message, marking the message as read if the logged in user is
+    /// the recipient
+    ///
+    /// The thread is the message along with the other messages between the same two users with
+    /// the same subject, ignoring any `Re:` prefix, oldest first. The rest of the thread is found
+    /// in the first page of the inbox and sent folder.
+    ///
+    /// Returns `Error::NotFound` if there is no message with this id.
+    pub fn message(
+        &self,
+        message_id: &MessageId,
+    ) -> impl Future<Item = Vec<Message>, Error = Error> {
+        let path = format!("messages/{}", message_id.0);
+        let others = self.inbox().join(self.sent_messages());
+
+        self.http
+            .get_json(&path)
+            .and_then(|res| {
+                if res.status == StatusCode::NOT_FOUND {
+                    Err(Error::NotFound)
+                } else if res.location().is_some() {
+                    Err(Error::Authorisation)
+                } else {
+                    res.json::<Message>()
+                }
+            })
+            .and_then(move |message| {
+                others.map(move |(inbox, sent)| {
+                    Self::message_thread(message, inbox.into_iter().chain(sent))
+                })
+            })
+    }
+
+    /// Send a private message
+    ///
+    /// If the site rejects the message `Error::Validation` is returned with the reasons.
+    pub fn send_message(&self, message: NewMessage) -> impl Future<Item = (), Error = Error> {
+        let params = [
+            ("message[recipient_username]", message.recipient_username),
+            ("message[subject]", message.subject),
+            ("message[body]", message.body),
+            ("message[hat_id]", message.hat_id.unwrap_or_default()),
+        ];
+
+        self.form_action("messages".to_string(), params)
+    }
+
+    /// Delete a message from the inbox or sent folder of the logged in user
+    pub fn delete_message(&self, message_id: &MessageId) -> impl Future<Item = (), Error = Error> {
+        let path = format!("messages/{}", message_id.0);
+        // Rails routes a POST with _method=delete to the destroy action
+        self.form_action(path, [("_method", "delete")])
+    }
+
+    /// Retrieve replies to the comments and stories of the logged in user, newest first
+    ///
+    /// The replies are parsed from HTML, see `search_comments` for the fields of the comments
+    /// that are missing.
+    pub fn replies(
+        &self,
+        filter: ReplyFilter,
+        page: Option<Page>,
+    ) -> impl Future<Item = Vec<Reply>, Error = Error> {
+        let path = Self::paginated_path(filter.path().to_string(), page);
+        let base_url = self.base_url().clone();
+
+        self.http.get(&path).and_then(move |res| {
+            // Requests that aren't logged in are redirected to the login page
+            if res.location().is_some() {
+                Err(Error::Authorisation)
+            } else {
+                res.text()
+                    .map(|body| scrape::replies_from_html(&base_url, body))
+            }
+        })
+    }
+
+    /// Upvote a story
+    pub fn upvote_story(&self, story_id: &StoryId) -> impl Future<Item = (), Error = Error> {
+        self.vote(format!("stories/{}/upvote", story_id.0), None)
+    }
+
+    /// Remove a vote or flag from a story
+    pub fn unvote_story(&self, story_id: &StoryId) -> impl Future<Item = (), Error = Error> {
+        self.vote(format!("stories/{}/unvote", story_id.0), None)
+    }
+
+    /// Flag a story for the supplied reason
+    pub fn flag_story(
+        &self,
+        story_id: &StoryId,
+        reason: StoryFlagReason,
+    ) -> impl Future<Item = (), Error = Error> {
+        self.vote(format!("stories/{}/flag", story_id.0), Some(reason.code()))
+    }
+
+    /// Save a story to read later
+    pub fn save_story(&self, story_id: &StoryId) -> impl Future<Item = (), Error = Error> {
+        self.action(format!("stories/{}/save", story_id.0), NO_PARAMS)
+    }
+
+    /// Remove a story from the saved stories
+    pub fn unsave_story(&self, story_id: &StoryId) -> impl Future<Item = (), Error = Error> {
+        self.action(format!("stories/{}/unsave", story_id.0), NO_PARAMS)
+    }
+
+    /// Hide a story from the listings of the logged in user
+    pub fn hide_story(&self, story_id: &StoryId) -> impl Future<Item = (), Error = Error> {
+        // The site redirects back to the story, rather than responding with a message
+        self.form_action(format!("stories/{}/hide", story_id.0), NO_PARAMS)
+    }
+
+    /// Show a hidden story in listings again
+    pub fn unhide_story(&self, story_id: &StoryId) -> impl Future<Item = (), Error = Error> {
+        self.form_action(format!("stories/{}/unhide", story_id.0), NO_PARAMS)
+    }
+
+    /// Retrieve the stories saved by the logged in user
+    pub fn saved_stories(
+        &self,
+        page: Option<Page>,
+    ) -> impl Future<Item = Vec<Story>, Error = Error> {
+        self.listing("saved".to_string(), page)
+    }
+
+    /// Retrieve the stories hidden by the logged in user
+    pub fn hidden_stories(
+        &self,
+        page: Option<Page>,
+    ) -> impl Future<Item = Vec<Story>, Error = Error> {
+        self.listing("hidden".to_string(), page)
+    }
+
+    /// Upvote a comment
+    pub fn upvote_comment(&self, comment_id: &CommentId) -> impl Future<Item = (), Error = Error> {
+        self.vote(format!("comments/{}/upvote", comment_id.0), None)
+    }
+
+    /// Remove a vote or flag from a comment
+    pub fn unvote_comment(&self, comment_id: &CommentId) -> impl Future<Item = (), Error = Error> {
+        self.vote(format!("comments/{}/unvote", comment_id.0), None)
+    }
+
+    /// Flag a comment for the supplied reason
+    pub fn flag_comment(
+        &self,
+        comment_id: &CommentId,
+        reason: CommentFlagReason,
+    ) -> impl Future<Item = (), Error = Error> {
+        self.vote(
+            format!("comments/{}/flag", comment_id.0),
+            Some(reason.code()),
+        )
     }
 
     /// Save the cookie store so that a client can be created without needing to log in first
+    ///
+    /// Does nothing if the client was built with an in-memory cookie store.
     pub fn save_cookies(&self) -> Result<(), Error> {
-        let cookie_store_path = cookie_store_path()?;
+        let cookie_store_path = match &self.cookie_store_path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
         let cookie_store_tmp_path = cookie_store_path.with_extension("tmp");
 
         // Ensure the directory the cookie file is stored in exists
         let config_dir = cookie_store_path.parent().ok_or_else(|| {
-            Error::Io(io::Error::new(
-                io::ErrorKind::Other,
-                "unable to find parent dir of cookie file",
-            ))
+            Error::Io(io::Error::other("unable to find parent dir of cookie file"))
         })?;
 
         if !config_dir.exists() {
@@ -218,21 +963,286 @@ impl Client {
         fs::rename(cookie_store_tmp_path, cookie_store_path).map_err(Error::from)
     }
 
-    /// The base URL of the remote site the client will communicate with
+    /// The path that the cookie store is saved to, `None` if it is only kept in memory
+    pub fn cookie_store_path(&self) -> Option<&Path> {
+        self.cookie_store_path.as_deref()
+    }
+
+    /// The base URL of the remote site the client will communicate with
     pub fn base_url(&self) -> &Url {
         self.http.base_url()
     }
 
-    fn extract_csrf_token(res: Response) -> impl Future<Item = String, Error = Error> {
-        res.into_body()
-            .concat2()
-            .map_err(Error::from)
-            .and_then(|body| {
-                std::str::from_utf8(&body)
-                    .map_err(|_err| Error::InvalidStr)
-                    .and_then(Self::extract_csrf_token_from_html)
+    /// Retrieve a page of the story listing at `path`
+    fn listing<T>(
+        &self,
+        path: String,
+        page: Option<Page>,
+    ) -> impl Future<Item = Vec<T>, Error = Error>
+    where
+        T: DeserializeOwned,
+    {
+        let path = Self::paginated_path(path, page);
+
+        self.http.get_json(&path).and_then(|res| {
+            // Listings that require a login redirect to the login page
+            if res.location().is_some() {
+                Err(Error::Authorisation)
+            } else {
+                res.json::<Vec<T>>()
+            }
+        })
+    }
+
+    /// The path of `page` of the listing at `path`
+    fn paginated_path(path: String, page: Option<Page>) -> String {
+        match page {
+            None => path,
+            Some(Page(page)) if path.is_empty() => format!("page/{}", page),
+            Some(Page(page)) => format!("{}/page/{}", path, page),
+        }
+    }
+
+    /// The stories of successive pages fetched by `fetch`, ending at the first empty page
+    fn paged_stories<F, R>(
+        mut fetch: F,
+        concurrency: usize,
+    ) -> impl Stream<Item = Story, Error = Error>
+    where
+        F: FnMut(Option<Page>) -> R,
+        R: Future<Item = Vec<Story>, Error = Error>,
+    {
+        let mut seen = HashSet::new();
+
+        // Failures are passed along as items so they stay in page order, otherwise a page
+        // fetched ahead of time could end the stream before an earlier empty page is reached
+        stream::iter_ok(1..)
+            .map(move |page| fetch(Page::new(page)).then(Ok))
+            .buffered(concurrency.max(1))
+            .take_while(|stories| {
+                Ok(match stories {
+                    Ok(stories) => !stories.is_empty(),
+                    Err(_) => true,
+                })
+            })
+            .and_then(|stories| stories)
+            .map(stream::iter_ok)
+            .flatten()
+            .filter(move |story| seen.insert(story.short_id.clone()))
+    }
+
+    fn search(
+        &self,
+        query: &str,
+        what: &str,
+        order: SearchOrder,
+        page: Option<Page>,
+    ) -> impl Future<Item = Response, Error = Error> {
+        let mut params = url::form_urlencoded::Serializer::new(String::new());
+        params
+            .append_pair("q", query)
+            .append_pair("what", what)
+            .append_pair("order", order.code());
+        if let Some(Page(page)) = page {
+            params.append_pair("page", &page.to_string());
+        }
+
+        self.http
+            .get(&format!("search?{}", params.finish()))
+            .and_then(|res| {
+                if res.status.is_success() && res.location().is_none() {
+                    Ok(res)
+                } else {
+                    Err(Self::response_error(&res))
+                }
             })
-            .into_future()
+    }
+
+    fn vote(
+        &self,
+        path: String,
+        reason: Option<&'static str>,
+    ) -> impl Future<Item = (), Error = Error> {
+        let params: Vec<_> = reason
+            .map(|reason| ("reason", reason))
+            .into_iter()
+            .collect();
+        self.action(path, params)
+    }
+
+    /// POST to an action such as voting, that responds with a short message or fragment of HTML
+    fn action<P>(&self, path: String, params: P) -> impl Future<Item = (), Error = Error>
+    where
+        P: Serialize + Send + 'static,
+    {
+        let client = self.http.clone();
+        let action = move |token| {
+            client
+                .post(&path, params, token)
+                .and_then(|res| Self::check_action_response(&res))
+        };
+
+        self.csrf_token().and_then(action)
+    }
+
+    /// Submit a form that redirects on success, like sending a message
+    fn form_action<P>(&self, path: String, params: P) -> impl Future<Item = (), Error = Error>
+    where
+        P: Serialize + Send + 'static,
+    {
+        let client = self.http.clone();
+        let action = move |token| {
+            client
+                .post(&path, params, token)
+                .and_then(|res| Self::check_form_response(&res))
+        };
+
+        self.csrf_token().and_then(action)
+    }
+
+    /// Fetch a CSRF token to be sent along with a POST request
+    fn csrf_token(&self) -> impl Future<Item = String, Error = Error> {
+        // Need to fetch a page to get a CSRF token, /about seems like one of the cheapest
+        // pages to fetch
+        self.http
+            .get("about")
+            .and_then(|res| Self::extract_csrf_token(&res))
+    }
+
+    /// Determine the outcome of an action such as voting, which responds with a short plain
+    /// text message
+    fn check_action_response(res: &Response) -> Result<(), Error> {
+        debug!("action body = {}", res.text().unwrap_or_default());
+        if res.status.is_success() && res.location().is_none() {
+            Ok(())
+        } else {
+            Err(Self::response_error(res))
+        }
+    }
+
+    /// Determine the outcome of submitting a form, which redirects on success and renders the
+    /// form again with errors on failure
+    fn check_form_response(res: &Response) -> Result<(), Error> {
+        match res.location() {
+            Some(location) if location.path() != "/login" => Ok(()),
+            _ => Err(Self::response_error(res)),
+        }
+    }
+
+    /// Fetch the comment created by a request that redirected to it
+    ///
+    /// The site redirects to the comment within its story, like `/s/abc123/title#c_def456`.
+    fn created_comment(&self, res: &Response) -> impl Future<Item = Comment, Error = Error> {
+        let location = res.location();
+        let story_id = location.as_ref().and_then(Self::story_id_from_url);
+        let comment_id = location.as_ref().and_then(Self::comment_id_from_url);
+
+        match (story_id, comment_id) {
+            (Some(story_id), Some(comment_id)) => {
+                let comment = self.story(&story_id).and_then(move |story| {
+                    story
+                        .comments
+                        .unwrap_or_default()
+                        .into_iter()
+                        .find(|comment| comment.short_id == comment_id)
+                        .ok_or(Error::NotFound)
+                });
+                Either::A(comment)
+            }
+            _ => Either::B(futures::future::err(Self::response_error(res))),
+        }
+    }
+
+    /// Build an error from a response that did not indicate success
+    fn response_error(res: &Response) -> Error {
+        if res.status == StatusCode::TOO_MANY_REQUESTS {
+            return Error::RateLimited;
+        }
+
+        // Requests that require a logged in user redirect to the login page or respond with
+        // "not logged in" when there isn't one
+        if res.location().is_some() {
+            return Error::Authorisation;
+        }
+
+        let body = match res.text() {
+            Ok(body) => body.trim(),
+            Err(err) => return err,
+        };
+        if body == "not logged in" {
+            return Error::Authorisation;
+        }
+
+        // Some failures are reported as JSON, others by re-rendering the form with the errors,
+        // the remainder are plain text messages
+        if let Ok(ErrorMessage { error }) = serde_json::from_str(body) {
+            return Error::Rejected(error);
+        }
+        let errors = Self::extract_form_errors_from_html(body);
+        if !errors.is_empty() {
+            Error::Validation(errors)
+        } else if !res.status.is_success() && !body.is_empty() {
+            Error::Rejected(body.to_string())
+        } else {
+            Error::MissingHtmlElement
+        }
+    }
+
+    /// Collect the messages from `others` that are in the same thread as `message`, oldest first
+    fn message_thread<I>(message: Message, others: I) -> Vec<Message>
+    where
+        I: IntoIterator<Item = Message>,
+    {
+        fn subject(message: &Message) -> &str {
+            let subject = message.subject.trim();
+            match subject.get(..3) {
+                Some(prefix) if prefix.eq_ignore_ascii_case("re:") => subject[3..].trim_start(),
+                _ => subject,
+            }
+        }
+
+        fn participants(message: &Message) -> [Option<&str>; 2] {
+            let mut users = [
+                message.author_username.as_deref(),
+                Some(message.recipient_username.as_str()),
+            ];
+            users.sort();
+            users
+        }
+
+        let mut thread: Vec<Message> = others
+            .into_iter()
+            .filter(|other| {
+                other.short_id != message.short_id
+                    && subject(other) == subject(&message)
+                    && participants(other) == participants(&message)
+            })
+            .collect();
+        thread.push(message);
+        thread.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        thread
+    }
+
+    /// Extract the short id from a story URL like `https://lobste.rs/s/abc123/title`
+    fn story_id_from_url(url: &Url) -> Option<StoryId> {
+        let mut segments = url.path_segments()?;
+        match (segments.next(), segments.next()) {
+            (Some("s"), Some(short_id)) if !short_id.is_empty() => {
+                Some(StoryId(short_id.to_string()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Extract the comment short id from the fragment of a URL like `/s/abc123/title#c_def456`
+    fn comment_id_from_url(url: &Url) -> Option<CommentId> {
+        url.fragment()
+            .filter(|fragment| fragment.starts_with("c_") && fragment.len() > 2)
+            .map(|fragment| CommentId(fragment[2..].to_string()))
+    }
+
+    fn extract_csrf_token(res: &Response) -> Result<String, Error> {
+        res.text().and_then(Self::extract_csrf_token_from_html)
     }
 
     fn extract_csrf_token_from_html(body: &str) -> Result<String, Error> {
@@ -245,6 +1255,47 @@ impl Client {
             })
             .ok_or_else(|| Error::MissingHtmlElement)
     }
+
+    fn extract_form_errors_from_html(body: &str) -> Vec<String> {
+        // Errors are listed in the flash-error div, usually one per list item
+        let html = kuchiki::parse_html().one(body);
+        let text = |node: kuchiki::NodeDataRef<kuchiki::ElementData>| {
+            node.text_contents().trim().to_string()
+        };
+        let items: Vec<_> = html
+            .select(".flash-error li")
+            .map(|items| items.map(text).collect())
+            .unwrap_or_default();
+
+        if items.is_empty() {
+            html.select(".flash-error")
+                .map(|errors| errors.map(text).filter(|error| !error.is_empty()).collect())
+                .unwrap_or_default()
+        } else {
+            items
+        }
+    }
+
+    fn extract_comment_source_from_html(body: &str) -> Result<String, Error> {
+        let html = kuchiki::parse_html().one(body);
+        html.select_first("textarea[name='comment']")
+            .map(|textarea| textarea.text_contents())
+            .map_err(|_err| Error::MissingHtmlElement)
+    }
+
+    fn extract_username_from_html(body: &str) -> Option<String> {
+        // When logged in the header contains a link to the user's profile
+        let html = kuchiki::parse_html().one(body);
+        html.select_first("#headerright a[href^='/u/']")
+            .ok()
+            .and_then(|link| {
+                let attrs = link.attributes.borrow();
+                attrs
+                    .get("href")
+                    .map(|href| href.trim_start_matches("/u/").to_string())
+            })
+            .filter(|username| !username.is_empty())
+    }
 }
 
 impl Page {
@@ -260,9 +1311,1267 @@ impl Page {
     }
 }
 
+impl Feed {
+    /// The path of the feed, relative to the base URL
+    fn path(self) -> String {
+        match self {
+            Feed::Hottest => "".to_string(),
+            Feed::Newest => "newest".to_string(),
+            Feed::Active => "active".to_string(),
+            Feed::Recent => "recent".to_string(),
+            Feed::Top(period) => format!("top/{}", period.code()),
+        }
+    }
+}
+
+impl ReplyFilter {
+    /// The path of the replies page, relative to the base URL
+    fn path(self) -> &'static str {
+        match self {
+            ReplyFilter::All => "replies",
+            ReplyFilter::Comments => "replies/comments",
+            ReplyFilter::Stories => "replies/stories",
+            ReplyFilter::Unread => "replies/unread",
+        }
+    }
+}
+
+impl SearchOrder {
+    /// The code the site uses for this order
+    fn code(self) -> &'static str {
+        match self {
+            SearchOrder::Relevance => "relevance",
+            SearchOrder::Newest => "newest",
+            SearchOrder::Points => "points",
+        }
+    }
+}
+
+impl TopPeriod {
+    /// The code the site uses for this period
+    fn code(self) -> &'static str {
+        match self {
+            TopPeriod::Day => "1d",
+            TopPeriod::Week => "1w",
+            TopPeriod::Month => "1m",
+            TopPeriod::Year => "1y",
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use reqwest::header::LOCATION;
+    use transport::{FakeTransport, Method};
+
+    const TOKEN_HTML: &str =
+        r#"<html><head><meta name="csrf-token" content="token" /></head></html>"#;
+
+    fn url(path: &str) -> Url {
+        Url::parse(crate::URL).unwrap().join(path).unwrap()
+    }
+
+    fn fake_client() -> (Client, FakeTransport) {
+        let transport = FakeTransport::new();
+        let client = ClientBuilder::new(url(""))
+            .in_memory_cookie_store()
+            .transport(transport.clone())
+            .build()
+            .unwrap();
+        (client, transport)
+    }
+
+    fn ok(path: &str, body: &str) -> Response {
+        Response::new(StatusCode::OK, url(path)).body(body)
+    }
+
+    fn redirect(path: &str, location: &str) -> Response {
+        Response::new(StatusCode::FOUND, url(path)).header(LOCATION, location)
+    }
+
+    #[test]
+    fn stories_feed_paths() {
+        let (client, transport) = fake_client();
+        let feeds = vec![
+            (Feed::Hottest, None, "/"),
+            (Feed::Hottest, Page::new(2), "/page/2"),
+            (Feed::Newest, None, "/newest"),
+            (Feed::Active, Page::new(3), "/active/page/3"),
+            (Feed::Recent, None, "/recent"),
+            (Feed::Top(TopPeriod::Month), Page::new(2), "/top/1m/page/2"),
+        ];
+
+        for (feed, page, path) in feeds {
+            transport.on(Method::Get, path, ok(path, "[]"));
+            assert!(client.stories(feed, page).wait().unwrap().is_empty());
+        }
+        assert!(transport
+            .requests()
+            .iter()
+            .all(|request| request.headers["accept"] == "application/json"));
+    }
+
+    fn stories_json(short_ids: &[&str]) -> String {
+        let stories: Vec<_> = short_ids
+            .iter()
+            .map(|short_id| STORY_JSON.replace("abc123", short_id))
+            .collect();
+        format!("[{}]", stories.join(","))
+    }
+
+    #[test]
+    fn story_urls() {
+        let (client, transport) = fake_client();
+        let text_story = STORY_JSON.replace(r#""url": "https://example.com/""#, r#""url": """#);
+        transport.on(Method::Get, "/s/abc123", ok("s/abc123", STORY_JSON));
+        transport.on(Method::Get, "/s/abc123", ok("s/abc123", &text_story));
+
+        let story_id = StoryId("abc123".to_string());
+        let story = client.story(&story_id).wait().unwrap();
+        assert_eq!(
+            story.url.as_ref().map(Url::as_str),
+            Some("https://example.com/")
+        );
+        let story = client.story(&story_id).wait().unwrap();
+        assert_eq!(story.url, None);
+    }
+
+    #[test]
+    fn story_invalid_url() {
+        let (client, transport) = fake_client();
+        let story = STORY_JSON.replace("https://example.com/", "not a url");
+        transport.on(Method::Get, "/s/abc123", ok("s/abc123", &story));
+
+        match client.story(&StoryId("abc123".to_string())).wait() {
+            Err(Error::Json(_)) => (),
+            other => panic!("Expected Error::Json got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn story_invalid_timestamp() {
+        let (client, transport) = fake_client();
+        let story = STORY_JSON.replace("2019-04-20T11:07:44.000-05:00", "yesterday");
+        transport.on(Method::Get, "/s/abc123", ok("s/abc123", &story));
+
+        match client.story(&StoryId("abc123".to_string())).wait() {
+            Err(Error::Json(_)) => (),
+            other => panic!("Expected Error::Json got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn story_stream() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/", ok("", &stories_json(&["a1", "b2"])));
+        // b2 moved down onto the second page between requests
+        transport.on(
+            Method::Get,
+            "/page/2",
+            ok("page/2", &stories_json(&["b2", "c3"])),
+        );
+        transport.on(Method::Get, "/page/3", ok("page/3", "[]"));
+
+        let stories = client
+            .story_stream(Feed::Hottest, 2)
+            .collect()
+            .wait()
+            .unwrap();
+        let short_ids: Vec<_> = stories
+            .iter()
+            .map(|story| story.short_id.0.as_str())
+            .collect();
+        assert_eq!(short_ids, vec!["a1", "b2", "c3"]);
+    }
+
+    #[test]
+    fn story_stream_is_lazy() {
+        let (client, transport) = fake_client();
+        transport.on(
+            Method::Get,
+            "/newest",
+            ok("newest", &stories_json(&["a1", "b2"])),
+        );
+
+        let stories = client
+            .story_stream(Feed::Newest, 1)
+            .take(2)
+            .collect()
+            .wait()
+            .unwrap();
+        assert_eq!(stories.len(), 2);
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[test]
+    fn saved_story_stream() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/saved", ok("saved", &stories_json(&["a1"])));
+        transport.on(
+            Method::Get,
+            "/saved/page/2",
+            ok("saved/page/2", &stories_json(&["b2"])),
+        );
+        transport.on(Method::Get, "/saved/page/3", ok("saved/page/3", "[]"));
+
+        let stories = client.saved_story_stream(1).collect().wait().unwrap();
+        assert_eq!(stories.len(), 2);
+        assert_eq!(transport.requests().len(), 3);
+    }
+
+    #[test]
+    fn tagged_and_domain_stories() {
+        let (client, transport) = fake_client();
+        transport.on(
+            Method::Get,
+            "/t/rust,go/page/2",
+            ok("t/rust,go/page/2", "[]"),
+        );
+        transport.on(
+            Method::Get,
+            "/domain/example.com",
+            ok("domain/example.com", "[]"),
+        );
+
+        let tags = [ShortTag("rust".to_string()), ShortTag("go".to_string())];
+        assert!(client
+            .tagged_stories(&tags, Page::new(2))
+            .wait()
+            .unwrap()
+            .is_empty());
+        assert!(client
+            .domain_stories("example.com", None)
+            .wait()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn tagged_stories_without_tags() {
+        let (client, transport) = fake_client();
+
+        match client.tagged_stories(&[], None).wait() {
+            Err(Error::NoTags) => (),
+            other => panic!("Expected Error::NoTags got {:?}", other),
+        }
+        assert!(transport.requests().is_empty());
+    }
+
+    const USER_JSON: &str = r#"{
+        "username": "alice",
+        "created_at": "2019-01-01T00:00:00.000-06:00",
+        "is_admin": false,
+        "about": "<p>Hi</p>",
+        "is_moderator": false,
+        "karma": 42,
+        "avatar_url": "/avatars/alice-100.png",
+        "invited_by_user": "bob",
+        "homepage": "https://example.com/",
+        "keybase_signatures": [{"kb_username": "alice", "sig_hash": "abc"}]
+    }"#;
+
+    #[test]
+    fn user() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/u/alice.json", ok("u/alice.json", USER_JSON));
+
+        let user = client.user("alice").wait().unwrap();
+        assert_eq!(user.username, "alice");
+        assert_eq!(user.karma, Some(42));
+        assert_eq!(user.homepage.as_deref(), Some("https://example.com/"));
+        assert_eq!(user.twitter_username, None);
+        assert_eq!(user.keybase_signatures[0].kb_username, "alice");
+    }
+
+    #[test]
+    fn user_not_found() {
+        let (client, _transport) = fake_client();
+
+        match client.user("nobody").wait() {
+            Err(Error::NotFound) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn user_activity() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/newest/alice", ok("newest/alice", "[]"));
+        let comments = format!("[{}]", COMMENT_JSON);
+        transport.on(
+            Method::Get,
+            "/threads/alice/page/2",
+            ok("threads/alice/page/2", &comments),
+        );
+
+        assert!(client
+            .user_stories("alice", None)
+            .wait()
+            .unwrap()
+            .is_empty());
+        let comments = client.user_threads("alice", Page::new(2)).wait().unwrap();
+        assert_eq!(comments[0].short_id, CommentId("def456".to_string()));
+    }
+
+    #[test]
+    fn login_success() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/login", ok("login", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/login",
+            redirect("login", "https://lobste.rs/lobsters-login-success"),
+        );
+
+        let state = client
+            .login("alice".to_string(), "hunter2".to_string())
+            .wait()
+            .unwrap();
+        match state {
+            LoginState::LoggedIn => (),
+            other => panic!("Expected LoginState::LoggedIn got {:?}", other),
+        }
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].headers["X-CSRF-Token"], "token");
+        assert_eq!(
+            requests[1].body.as_ref().unwrap(),
+            "email=alice&password=hunter2&referer=https%3A%2F%2Flobste.rs%2Flobsters-login-success"
+        );
+    }
+
+    #[test]
+    fn login_failure() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/login", ok("login", TOKEN_HTML));
+        transport.on(Method::Post, "/login", ok("login", TOKEN_HTML));
+
+        match client
+            .login("alice".to_string(), "wrong".to_string())
+            .wait()
+        {
+            Err(Error::Authorisation) => (),
+            other => panic!("Expected Error::Authorisation got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn login_two_factor() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/login", ok("login", TOKEN_HTML));
+        transport.on(Method::Post, "/login", redirect("login", "/login/2fa"));
+        transport.on(
+            Method::Get,
+            "/login/2fa",
+            ok(
+                "login/2fa",
+                r#"<html><head><meta name="csrf-token" content="2fa-token" /></head></html>"#,
+            ),
+        );
+        transport.on(
+            Method::Post,
+            "/login/2fa_verify",
+            redirect("login/2fa_verify", "/"),
+        );
+
+        let challenge = match client
+            .login("alice".to_string(), "hunter2".to_string())
+            .wait()
+            .unwrap()
+        {
+            LoginState::TwoFactorRequired(challenge) => challenge,
+            other => panic!("Expected LoginState::TwoFactorRequired got {:?}", other),
+        };
+        client
+            .login_2fa(challenge, "123456".to_string())
+            .wait()
+            .unwrap();
+
+        let requests = transport.requests();
+        assert_eq!(requests[3].url, url("login/2fa_verify"));
+        assert_eq!(requests[3].headers["X-CSRF-Token"], "2fa-token");
+        assert_eq!(requests[3].body.as_ref().unwrap(), "totp_code=123456");
+    }
+
+    #[test]
+    fn login_two_factor_incorrect_code() {
+        let (client, transport) = fake_client();
+        transport.on(
+            Method::Post,
+            "/login/2fa_verify",
+            redirect("login/2fa_verify", "/login/2fa"),
+        );
+
+        let challenge = TwoFactorChallenge {
+            csrf_token: "token".to_string(),
+        };
+        match client.login_2fa(challenge, "000000".to_string()).wait() {
+            Err(Error::Authorisation) => (),
+            other => panic!("Expected Error::Authorisation got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn login_two_factor_expired() {
+        let (client, transport) = fake_client();
+        transport.on(
+            Method::Post,
+            "/login/2fa_verify",
+            redirect("login/2fa_verify", "/login"),
+        );
+
+        let challenge = TwoFactorChallenge {
+            csrf_token: "token".to_string(),
+        };
+        match client.login_2fa(challenge, "123456".to_string()).wait() {
+            Err(Error::Authorisation) => (),
+            other => panic!("Expected Error::Authorisation got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn logout() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(Method::Post, "/logout", redirect("logout", "/"));
+
+        client.logout().wait().unwrap();
+
+        let requests = transport.requests();
+        assert_eq!(requests[1].method, Method::Post);
+        assert_eq!(requests[1].url, url("logout"));
+    }
+
+    #[test]
+    fn logout_rejected() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/logout",
+            Response::new(StatusCode::UNPROCESSABLE_ENTITY, url("logout"))
+                .body("Invalid authenticity token"),
+        );
+
+        match client.logout().wait() {
+            Err(Error::Rejected(message)) => assert_eq!(message, "Invalid authenticity token"),
+            other => panic!("Expected Error::Rejected got {:?}", other),
+        }
+    }
+
+    fn new_comment() -> NewComment {
+        NewComment {
+            story_id: StoryId("abc123".to_string()),
+            comment: "Hello".to_string(),
+            hat_id: None,
+            parent_comment_short_id: Some(CommentId("xyz789".to_string())),
+        }
+    }
+
+    #[test]
+    fn post_comment() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/comments",
+            redirect("comments", "/s/abc123/a_story#c_def456"),
+        );
+        let story = STORY_JSON.replace(
+            r#""comments": []"#,
+            &format!("\"comments\": [{}]", COMMENT_JSON),
+        );
+        transport.on(Method::Get, "/s/abc123", ok("s/abc123", &story));
+
+        let comment = client.post_comment(new_comment()).wait().unwrap();
+        assert_eq!(comment.short_id, CommentId("def456".to_string()));
+        assert_eq!(comment.comment, "<p>Hello</p>");
+
+        let requests = transport.requests();
+        assert_eq!(
+            requests[1].body.as_ref().unwrap(),
+            "story_id=abc123&comment=Hello&parent_comment_short_id=xyz789"
+        );
+    }
+
+    #[test]
+    fn post_comment_validation_errors() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/comments",
+            ok(
+                "comments",
+                r#"<div class="comment_form_container"><div class="flash-error">
+                <ul><li>You have already posted a comment here recently.</li></ul></div>
+                <textarea name="comment">Hello</textarea></div>"#,
+            ),
+        );
+
+        match client.post_comment(new_comment()).wait() {
+            Err(Error::Validation(errors)) => assert_eq!(
+                errors,
+                vec!["You have already posted a comment here recently.".to_string()]
+            ),
+            other => panic!("Expected Error::Validation got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn post_comment_rejected() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/comments",
+            ok(
+                "comments",
+                r#"{"error":"invalid parent comment","status":400}"#,
+            ),
+        );
+
+        match client.post_comment(new_comment()).wait() {
+            Err(Error::Rejected(ref message)) if message == "invalid parent comment" => (),
+            other => panic!("Expected Error::Rejected got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn post_comment_rate_limited() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/comments",
+            Response::new(StatusCode::TOO_MANY_REQUESTS, url("comments")).body("Retry later"),
+        );
+
+        match client.post_comment(new_comment()).wait() {
+            Err(Error::RateLimited) => (),
+            other => panic!("Expected Error::RateLimited got {:?}", other),
+        }
+    }
+
+    const STORY_JSON: &str = r#"{
+        "short_id": "abc123",
+        "short_id_url": "https://lobste.rs/s/abc123",
+        "created_at": "2019-04-20T11:07:44.000-05:00",
+        "title": "A story",
+        "url": "https://example.com/",
+        "score": 1,
+        "upvotes": 1,
+        "downvotes": 0,
+        "comment_count": 0,
+        "description": "",
+        "comments_url": "https://lobste.rs/s/abc123/a_story",
+        "submitter_user": {
+            "username": "alice",
+            "created_at": "2018-01-01T00:00:00.000-05:00",
+            "is_admin": false,
+            "about": "",
+            "is_moderator": false,
+            "karma": 10,
+            "avatar_url": "/avatars/alice-100.png",
+            "invited_by_user": "bob"
+        },
+        "tags": ["rust"],
+        "comments": []
+    }"#;
+
+    const COMMENT_JSON: &str = r#"{
+        "short_id": "def456",
+        "short_id_url": "https://lobste.rs/c/def456",
+        "created_at": "2019-04-21T11:07:44.000-05:00",
+        "updated_at": "2019-04-21T11:07:44.000-05:00",
+        "is_deleted": false,
+        "is_moderated": false,
+        "score": 1,
+        "upvotes": 1,
+        "downvotes": 0,
+        "comment": "<p>Hello</p>",
+        "url": "https://lobste.rs/s/abc123/a_story#c_def456",
+        "indent_level": 1,
+        "commenting_user": {
+            "username": "alice",
+            "created_at": "2018-01-01T00:00:00.000-05:00",
+            "is_admin": false,
+            "about": "",
+            "is_moderator": false,
+            "karma": 10,
+            "avatar_url": "/avatars/alice-100.png",
+            "invited_by_user": "bob"
+        }
+    }"#;
+
+    fn new_story() -> NewStory {
+        NewStory {
+            url: Some("https://example.com/".to_string()),
+            title: "A story".to_string(),
+            description: None,
+            tags: vec![ShortTag("rust".to_string()), ShortTag("web".to_string())],
+            user_is_author: true,
+        }
+    }
+
+    #[test]
+    fn submit_story() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/stories",
+            redirect("stories", "/s/abc123/a_story"),
+        );
+        transport.on(Method::Get, "/s/abc123", ok("s/abc123", STORY_JSON));
+
+        let story = client.submit_story(new_story()).wait().unwrap();
+        assert_eq!(story.short_id, StoryId("abc123".to_string()));
+
+        let requests = transport.requests();
+        assert_eq!(
+            requests[1].body.as_ref().unwrap(),
+            "story%5Btitle%5D=A+story&story%5Burl%5D=https%3A%2F%2Fexample.com%2F\
+             &story%5Bdescription%5D=&story%5Buser_is_author%5D=1\
+             &story%5Btags_a%5D%5B%5D=rust&story%5Btags_a%5D%5B%5D=web"
+        );
+    }
+
+    #[test]
+    fn submit_story_validation_errors() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/stories",
+            ok(
+                "stories",
+                r#"<html><body><div class="flash-error">
+                <h2>2 errors prohibited this story from being saved</h2>
+                <p>There were the problems with the following fields:</p>
+                <ul><li>URL has already been submitted within the past 30 days</li>
+                <li>Tags web is not a valid tag</li></ul>
+                </div></body></html>"#,
+            ),
+        );
+
+        match client.submit_story(new_story()).wait() {
+            Err(Error::Validation(errors)) => assert_eq!(
+                errors,
+                vec![
+                    "URL has already been submitted within the past 30 days".to_string(),
+                    "Tags web is not a valid tag".to_string()
+                ]
+            ),
+            other => panic!("Expected Error::Validation got {:?}", other),
+        }
+    }
+
+    const MESSAGE_JSON: &str = r#"{
+        "short_id": "m1a2b3",
+        "created_at": "2019-04-21T11:07:44.000-05:00",
+        "has_been_read": false,
+        "subject": "Hello",
+        "body": "How are you?",
+        "deleted_by_author": false,
+        "deleted_by_recipient": false,
+        "author_username": "bob",
+        "recipient_username": "alice"
+    }"#;
+
+    fn new_message() -> NewMessage {
+        NewMessage {
+            recipient_username: "bob".to_string(),
+            subject: "Re: Hello".to_string(),
+            body: "Good thanks".to_string(),
+            hat_id: None,
+        }
+    }
+
+    #[test]
+    fn inbox() {
+        let (client, transport) = fake_client();
+        let messages = format!("[{}]", MESSAGE_JSON);
+        transport.on(Method::Get, "/messages", ok("messages", &messages));
+
+        let messages = client.inbox().wait().unwrap();
+        assert_eq!(messages[0].short_id, MessageId("m1a2b3".to_string()));
+        assert_eq!(messages[0].author_username.as_deref(), Some("bob"));
+        assert!(!messages[0].has_been_read);
+    }
+
+    #[test]
+    fn sent_messages_not_logged_in() {
+        let (client, transport) = fake_client();
+        transport.on(
+            Method::Get,
+            "/messages/sent",
+            redirect("messages/sent", "/login"),
+        );
+
+        match client.sent_messages().wait() {
+            Err(Error::Authorisation) => (),
+            other => panic!("Expected Error::Authorisation got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn message() {
+        let (client, transport) = fake_client();
+        let inbox = r#"[{
+            "short_id": "m4d5e6",
+            "created_at": "2019-04-23T09:00:00.000-05:00",
+            "subject": "Hello",
+            "body": "Are you there?",
+            "author_username": "bob",
+            "recipient_username": "alice"
+        }, {
+            "short_id": "m7f8a9",
+            "created_at": "2019-04-22T09:00:00.000-05:00",
+            "subject": "Hello",
+            "body": "Hi from carol",
+            "author_username": "carol",
+            "recipient_username": "alice"
+        }]"#;
+        let sent = r#"[{
+            "short_id": "mb1c2d",
+            "created_at": "2019-04-22T10:00:00.000-05:00",
+            "subject": "Re: Hello",
+            "body": "Good thanks",
+            "author_username": "alice",
+            "recipient_username": "bob"
+        }]"#;
+        transport.on(
+            Method::Get,
+            "/messages/m1a2b3",
+            ok("messages/m1a2b3", MESSAGE_JSON),
+        );
+        transport.on(Method::Get, "/messages", ok("messages", inbox));
+        transport.on(Method::Get, "/messages/sent", ok("messages/sent", sent));
+
+        let thread = client
+            .message(&MessageId("m1a2b3".to_string()))
+            .wait()
+            .unwrap();
+        let ids: Vec<_> = thread
+            .iter()
+            .map(|message| message.short_id.0.as_str())
+            .collect();
+        assert_eq!(ids, vec!["m1a2b3", "mb1c2d", "m4d5e6"]);
+        assert_eq!(thread[0].subject, "Hello");
+    }
+
+    #[test]
+    fn message_not_found() {
+        let (client, _transport) = fake_client();
+
+        match client.message(&MessageId("m1a2b3".to_string())).wait() {
+            Err(Error::NotFound) => (),
+            other => panic!("Expected Error::NotFound got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn send_message() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(Method::Post, "/messages", redirect("messages", "/messages"));
+
+        client.send_message(new_message()).wait().unwrap();
+
+        let requests = transport.requests();
+        assert_eq!(
+            requests[1].body.as_ref().unwrap(),
+            "message%5Brecipient_username%5D=bob&message%5Bsubject%5D=Re%3A+Hello\
+             &message%5Bbody%5D=Good+thanks&message%5Bhat_id%5D="
+        );
+    }
+
+    #[test]
+    fn send_message_validation_errors() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/messages",
+            ok(
+                "messages",
+                r#"<html><body><div class="flash-error">
+                <ul><li>Recipient username is not a valid user</li></ul>
+                </div></body></html>"#,
+            ),
+        );
+
+        match client.send_message(new_message()).wait() {
+            Err(Error::Validation(errors)) => {
+                assert_eq!(errors, vec!["Recipient username is not a valid user"])
+            }
+            other => panic!("Expected Error::Validation got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn delete_message() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/messages/m1a2b3",
+            redirect("messages/m1a2b3", "/messages"),
+        );
+
+        client
+            .delete_message(&MessageId("m1a2b3".to_string()))
+            .wait()
+            .unwrap();
+        assert_eq!(
+            transport.requests()[1].body.as_ref().unwrap(),
+            "_method=delete"
+        );
+    }
+
+    #[test]
+    fn replies() {
+        let (client, transport) = fake_client();
+        transport.on(
+            Method::Get,
+            "/replies/unread",
+            ok("replies/unread", "<html></html>"),
+        );
+        transport.on(
+            Method::Get,
+            "/replies/page/2",
+            redirect("replies/page/2", "/login"),
+        );
+
+        assert!(client
+            .replies(ReplyFilter::Unread, None)
+            .wait()
+            .unwrap()
+            .is_empty());
+        match client.replies(ReplyFilter::All, Page::new(2)).wait() {
+            Err(Error::Authorisation) => (),
+            other => panic!("Expected Error::Authorisation got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn save_and_hide_story() {
+        let (client, transport) = fake_client();
+        let story_id = StoryId("abc123".to_string());
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/stories/abc123/save",
+            ok("stories/abc123/save", "ok"),
+        );
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/stories/abc123/hide",
+            redirect("stories/abc123/hide", "/s/abc123/a_story"),
+        );
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/stories/abc123/unhide",
+            redirect("stories/abc123/unhide", "/login"),
+        );
+
+        client.save_story(&story_id).wait().unwrap();
+        client.hide_story(&story_id).wait().unwrap();
+        match client.unhide_story(&story_id).wait() {
+            Err(Error::Authorisation) => (),
+            other => panic!("Expected Error::Authorisation got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn saved_stories_not_logged_in() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/saved", redirect("saved", "/login"));
+
+        match client.saved_stories(None).wait() {
+            Err(Error::Authorisation) => (),
+            other => panic!("Expected Error::Authorisation got {:?}", other),
+        }
+    }
+
+    fn new_invitation() -> NewInvitation {
+        NewInvitation {
+            email: "carol@example.com".to_string(),
+            memo: "From the team chat".to_string(),
+        }
+    }
+
+    #[test]
+    fn send_invitation() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/invitations",
+            redirect("invitations", "/settings"),
+        );
+        transport.on(
+            Method::Get,
+            "/settings",
+            ok(
+                "settings",
+                r#"<html><body><div class="flash-success">
+                Successfully e-mailed invitation to carol@example.com.</div></body></html>"#,
+            ),
+        );
+
+        client.send_invitation(new_invitation()).wait().unwrap();
+
+        let requests = transport.requests();
+        assert_eq!(
+            requests[1].body.as_ref().unwrap(),
+            "email=carol%40example.com&memo=From+the+team+chat"
+        );
+    }
+
+    #[test]
+    fn send_invitation_error() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/invitations",
+            redirect("invitations", "/settings"),
+        );
+        transport.on(
+            Method::Get,
+            "/settings",
+            ok(
+                "settings",
+                r#"<html><body><div class="flash-error">
+                Could not send invitation, verify the e-mail address is valid.
+                </div></body></html>"#,
+            ),
+        );
+
+        match client.send_invitation(new_invitation()).wait() {
+            Err(Error::Validation(errors)) => assert_eq!(
+                errors,
+                vec!["Could not send invitation, verify the e-mail address is valid."]
+            ),
+            other => panic!("Expected Error::Validation got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn send_invitation_not_logged_in() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/invitations",
+            redirect("invitations", "/login"),
+        );
+
+        match client.send_invitation(new_invitation()).wait() {
+            Err(Error::Authorisation) => (),
+            other => panic!("Expected Error::Authorisation got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn set_filtered_tags() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(Method::Post, "/filters", redirect("filters", "/filters"));
+
+        let tags = vec![ShortTag("php".to_string()), ShortTag("java".to_string())];
+        client.set_filtered_tags(&tags).wait().unwrap();
+
+        let requests = transport.requests();
+        assert_eq!(
+            requests[1].body.as_ref().unwrap(),
+            "tags%5B%5D=php&tags%5B%5D=java"
+        );
+    }
+
+    #[test]
+    fn moderations_filtered() {
+        let (client, transport) = fake_client();
+        let path = "/moderations?moderator=alice&what%5Bstories%5D=stories\
+                    &what%5Busers%5D=users&page=3";
+        transport.on(Method::Get, path, ok(path, "<html><body></body></html>"));
+
+        let filter = ModerationFilter {
+            moderator: Some("alice".to_string()),
+            targets: vec![ModerationTarget::Story, ModerationTarget::User],
+        };
+        let moderations = client
+            .moderations(&filter, Page::new(3))
+            .wait()
+            .expect("moderations error");
+        assert!(moderations.is_empty());
+    }
+
+    #[test]
+    fn moderations_rate_limited() {
+        let (client, transport) = fake_client();
+        transport.on(
+            Method::Get,
+            "/moderations",
+            Response::new(StatusCode::TOO_MANY_REQUESTS, url("moderations")),
+        );
+
+        match client
+            .moderations(&ModerationFilter::default(), None)
+            .wait()
+        {
+            Err(Error::RateLimited) => (),
+            other => panic!("Expected Error::RateLimited got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn wearable_hats_not_logged_in() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/messages", redirect("messages", "/login"));
+
+        match client.wearable_hats().wait() {
+            Err(Error::Authorisation) => (),
+            other => panic!("Expected Error::Authorisation got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn upvote_story() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(Method::Post, "/stories/abc123/upvote", ok("", "ok"));
+
+        client
+            .upvote_story(&StoryId("abc123".to_string()))
+            .wait()
+            .unwrap();
+    }
+
+    #[test]
+    fn flag_comment_sends_reason() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(Method::Post, "/comments/def456/flag", ok("", "ok"));
+
+        client
+            .flag_comment(&CommentId("def456".to_string()), CommentFlagReason::Troll)
+            .wait()
+            .unwrap();
+
+        let requests = transport.requests();
+        assert_eq!(requests[1].body.as_ref().unwrap(), "reason=T");
+    }
+
+    #[test]
+    fn vote_rejected() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/stories/abc123/upvote",
+            Response::new(StatusCode::BAD_REQUEST, url("")).body("can't find story"),
+        );
+
+        match client.upvote_story(&StoryId("abc123".to_string())).wait() {
+            Err(Error::Rejected(ref message)) if message == "can't find story" => (),
+            other => panic!("Expected Error::Rejected got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn vote_logged_out() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/stories/abc123/unvote",
+            redirect("stories/abc123/unvote", "/login"),
+        );
+
+        match client.unvote_story(&StoryId("abc123".to_string())).wait() {
+            Err(Error::Authorisation) => (),
+            other => panic!("Expected Error::Authorisation got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn comment_source() {
+        let (client, transport) = fake_client();
+        transport.on(
+            Method::Get,
+            "/comments/def456/edit",
+            ok(
+                "comments/def456/edit",
+                r#"<div class="comment_form_container"><form>
+                <textarea name="comment">Hello *world* &amp; all</textarea>
+                </form></div>"#,
+            ),
+        );
+
+        let source = client
+            .comment_source(&CommentId("def456".to_string()))
+            .wait()
+            .unwrap();
+        assert_eq!(source, "Hello *world* & all");
+    }
+
+    #[test]
+    fn comment_source_not_editable() {
+        let (client, transport) = fake_client();
+        transport.on(
+            Method::Get,
+            "/comments/def456/edit",
+            Response::new(StatusCode::BAD_REQUEST, url("comments/def456/edit"))
+                .body("can't find comment"),
+        );
+
+        match client
+            .comment_source(&CommentId("def456".to_string()))
+            .wait()
+        {
+            Err(Error::Rejected(ref message)) if message == "can't find comment" => (),
+            other => panic!("Expected Error::Rejected got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn update_comment() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/comments/def456",
+            ok(
+                "comments/def456",
+                r#"<div class="comment" id="c_def456"><p>Goodbye</p></div>"#,
+            ),
+        );
+        transport.on(
+            Method::Get,
+            "/comments/def456/edit",
+            ok(
+                "comments/def456/edit",
+                "<textarea name=\"comment\">Goodbye\r\n</textarea>",
+            ),
+        );
+
+        client
+            .update_comment(&CommentId("def456".to_string()), "Goodbye".to_string())
+            .wait()
+            .unwrap();
+
+        let requests = transport.requests();
+        assert_eq!(
+            requests[1].body.as_ref().unwrap(),
+            "_method=patch&comment=Goodbye"
+        );
+    }
+
+    #[test]
+    fn update_comment_not_saved() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/comments/def456",
+            ok("comments/def456", "<p>Something went wrong</p>"),
+        );
+        transport.on(
+            Method::Get,
+            "/comments/def456/edit",
+            ok(
+                "comments/def456/edit",
+                r#"<textarea name="comment">Hello</textarea>"#,
+            ),
+        );
+
+        match client
+            .update_comment(&CommentId("def456".to_string()), "Goodbye".to_string())
+            .wait()
+        {
+            Err(Error::MissingHtmlElement) => (),
+            other => panic!("Expected Error::MissingHtmlElement got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn update_comment_redirected_to_login() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/comments/def456",
+            redirect("comments/def456", "/login"),
+        );
+
+        match client
+            .update_comment(&CommentId("def456".to_string()), "Goodbye".to_string())
+            .wait()
+        {
+            Err(Error::Authorisation) => (),
+            other => panic!("Expected Error::Authorisation got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn update_comment_validation_errors() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/comments/def456",
+            ok(
+                "comments/def456",
+                r#"<div class="comment_form_container"><div class="flash-error">
+                <ul><li>Comment cannot be blank.</li></ul></div>
+                <textarea name="comment"></textarea></div>"#,
+            ),
+        );
+        transport.on(
+            Method::Get,
+            "/comments/def456/edit",
+            ok(
+                "comments/def456/edit",
+                r#"<textarea name="comment">Hello</textarea>"#,
+            ),
+        );
+
+        match client
+            .update_comment(&CommentId("def456".to_string()), "".to_string())
+            .wait()
+        {
+            Err(Error::Validation(errors)) => {
+                assert_eq!(errors, vec!["Comment cannot be blank.".to_string()])
+            }
+            other => panic!("Expected Error::Validation got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn delete_comment() {
+        let (client, transport) = fake_client();
+        transport.on(Method::Get, "/about", ok("about", TOKEN_HTML));
+        transport.on(
+            Method::Post,
+            "/comments/def456/delete",
+            ok(
+                "comments/def456/delete",
+                r#"<div class="comment deleted"></div>"#,
+            ),
+        );
+
+        client
+            .delete_comment(&CommentId("def456".to_string()))
+            .wait()
+            .unwrap();
+    }
 
     #[test]
     fn extract_csrf_token_from_html_success() {
@@ -281,4 +2590,126 @@ mod tests {
             other => panic!("Expected Error::MissingHtmlElement got {:?}", other),
         }
     }
+
+    #[test]
+    fn cookie_store_path_per_site_and_profile() {
+        let url = Url::parse("https://lobste.rs/").unwrap();
+        let path = cookie_store_path(&url, "work").unwrap();
+        assert!(path.ends_with("cookies/lobste.rs/work.json"));
+
+        let url = Url::parse("http://localhost:3000/").unwrap();
+        let path = cookie_store_path(&url, DEFAULT_PROFILE).unwrap();
+        assert!(path.ends_with("cookies/localhost_3000/default.json"));
+    }
+
+    #[test]
+    fn cookie_store_path_invalid_profile() {
+        let url = Url::parse("https://lobste.rs/").unwrap();
+        for profile in &["", "../other", ".hidden", "a/b"] {
+            match cookie_store_path(&url, profile) {
+                Err(Error::InvalidProfile(_)) => (),
+                other => panic!("Expected Error::InvalidProfile got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn search_stories() {
+        let (client, transport) = fake_client();
+        let path = "/search?q=rust+async&what=stories&order=newest&page=2";
+        transport.on(Method::Get, path, ok(path, "<html></html>"));
+
+        let stories = client
+            .search_stories("rust async", SearchOrder::Newest, Page::new(2))
+            .wait()
+            .unwrap();
+        assert!(stories.is_empty());
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[test]
+    fn search_comments_redirected_to_login() {
+        let (client, transport) = fake_client();
+        let path = "/search?q=rust&what=comments&order=relevance";
+        transport.on(Method::Get, path, redirect(path, "/login"));
+
+        match client
+            .search_comments("rust", SearchOrder::Relevance, None)
+            .wait()
+        {
+            Err(Error::Authorisation) => (),
+            other => panic!("Expected Error::Authorisation got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn search_stories_rate_limited() {
+        let (client, transport) = fake_client();
+        let path = "/search?q=rust&what=stories&order=relevance";
+        transport.on(
+            Method::Get,
+            path,
+            Response::new(StatusCode::TOO_MANY_REQUESTS, url(path)),
+        );
+
+        match client
+            .search_stories("rust", SearchOrder::Relevance, None)
+            .wait()
+        {
+            Err(Error::RateLimited) => (),
+            other => panic!("Expected Error::RateLimited got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn recent_comments() {
+        let (client, transport) = fake_client();
+        let comments = format!("[{}]", COMMENT_JSON);
+        let html = r#"<html><body><ol class="comments"><li>
+            <div class="comment" id="c_def456" data-shortid="def456">
+            <div class="details"><div class="byline">
+            <a href="/u/alice">alice</a>
+            <a href="/s/abc123/a_story#c_def456">link</a>
+            on: <a href="/s/abc123/a_story">A Story</a>
+            </div></div></div></li></ol></body></html>"#;
+        // The JSON is requested first, then the HTML
+        transport.on(
+            Method::Get,
+            "/comments/page/2",
+            ok("comments/page/2", &comments),
+        );
+        transport.on(Method::Get, "/comments/page/2", ok("comments/page/2", html));
+
+        let comments = client.recent_comments(Page::new(2)).wait().unwrap();
+        let requests = transport.requests();
+        assert_eq!(
+            requests[0].headers.get("accept").unwrap(),
+            "application/json"
+        );
+        assert!(requests[1].headers.get("accept").is_none());
+        assert_eq!(comments[0].short_id, CommentId("def456".to_string()));
+        assert_eq!(comments[0].upvotes, 1);
+        assert_eq!(comments[0].story_id, Some(StoryId("abc123".to_string())));
+        assert_eq!(comments[0].story_title.as_deref(), Some("A Story"));
+    }
+
+    #[test]
+    fn extract_username_from_html_logged_in() {
+        let html = r#"<html><body><div id="headerright"><span class="headerlinks">
+            <a href="/messages">Inbox</a>
+            <a href="/u/wezm">wezm (123)</a>
+            </span></div></body></html>"#;
+        assert_eq!(
+            Client::extract_username_from_html(html),
+            Some("wezm".to_string())
+        );
+    }
+
+    #[test]
+    fn extract_username_from_html_logged_out() {
+        let html = r#"<html><body><div id="headerright" class="nobody"><span class="headerlinks">
+            <a href="/login">Login</a>
+            </span></div></body></html>"#;
+        assert_eq!(Client::extract_username_from_html(html), None);
+    }
 }
@@ -0,0 +1,186 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use cookie_store::CookieStore;
+use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
+use reqwest::r#async::ClientBuilder as ReqwestClientBuilder;
+use reqwest::{Proxy, RedirectPolicy};
+use url::Url;
+
+use crate::error::Error;
+
+use super::http_client::HttpClient;
+use super::session::Session;
+use super::transport::Transport;
+use super::{cookie_store_path, legacy_cookie_store_path, load_cookies, Client, DEFAULT_PROFILE};
+
+/// A builder to configure a `Client`
+///
+/// Example:
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use lobsters::{ClientBuilder, url::Url};
+///
+/// let client = ClientBuilder::new(Url::parse(lobsters::URL).unwrap())
+///     .profile("work")
+///     .timeout(Duration::from_secs(30))
+///     .user_agent("digest-bot/1.0 (ops@example.com)")
+///     .build()
+///     .unwrap();
+/// ```
+pub struct ClientBuilder {
+    base_url: Url,
+    cookie_store: CookieStoreLocation,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    user_agent: Option<String>,
+    proxy: Option<Url>,
+    default_headers: HeaderMap,
+    transport: Option<Arc<dyn Transport>>,
+}
+
+/// Where the cookie store is loaded from and saved to
+enum CookieStoreLocation {
+    Profile(String),
+    Path(PathBuf),
+    Memory,
+}
+
+impl ClientBuilder {
+    /// Create a new builder for a client of the site at `base_url`
+    pub fn new(base_url: Url) -> Self {
+        ClientBuilder {
+            base_url,
+            cookie_store: CookieStoreLocation::Profile(DEFAULT_PROFILE.to_string()),
+            timeout: None,
+            connect_timeout: None,
+            user_agent: None,
+            proxy: None,
+            default_headers: HeaderMap::new(),
+            transport: None,
+        }
+    }
+
+    /// Use the cookie store of the named profile
+    ///
+    /// Each site has its own set of profiles. When no cookie store is chosen the default profile
+    /// is used.
+    pub fn profile<S: Into<String>>(mut self, profile: S) -> Self {
+        self.cookie_store = CookieStoreLocation::Profile(profile.into());
+        self
+    }
+
+    /// Load and save the cookie store at `path`
+    pub fn cookie_store_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.cookie_store = CookieStoreLocation::Path(path.into());
+        self
+    }
+
+    /// Keep cookies in memory only, they are never loaded from or saved to disk
+    pub fn in_memory_cookie_store(mut self) -> Self {
+        self.cookie_store = CookieStoreLocation::Memory;
+        self
+    }
+
+    /// Set a timeout for each request, from connecting until the response body is read
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set a timeout for connecting to the server
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the User-Agent header sent with each request
+    pub fn user_agent<S: Into<String>>(mut self, user_agent: S) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Send all requests through the proxy at `url`
+    ///
+    /// HTTP and HTTPS proxies are supported. SOCKS5 proxies (`socks5://`) require the `socks`
+    /// feature.
+    pub fn proxy(mut self, url: Url) -> Self {
+        self.proxy = Some(url);
+        self
+    }
+
+    /// Add headers that will be sent with each request
+    pub fn default_headers(mut self, headers: HeaderMap) -> Self {
+        self.default_headers.extend(headers);
+        self
+    }
+
+    /// Send requests through `transport` instead of the default `reqwest` based one
+    ///
+    /// The timeout, user agent, proxy and default header options are not applied to a custom
+    /// transport.
+    pub fn transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Build the client
+    ///
+    /// Will attempt to load the cookie store if it exists.
+    pub fn build(self) -> Result<Client, Error> {
+        let (cookie_store_path, cookies) = match self.cookie_store {
+            CookieStoreLocation::Profile(profile) => {
+                let path = cookie_store_path(&self.base_url, &profile)?;
+
+                // Fall back on the store shared by all sites in earlier versions
+                let cookies = if profile == DEFAULT_PROFILE && !path.exists() {
+                    load_cookies(&legacy_cookie_store_path()?)?
+                } else {
+                    load_cookies(&path)?
+                };
+                (Some(path), cookies)
+            }
+            CookieStoreLocation::Path(path) => {
+                let cookies = load_cookies(&path)?;
+                (Some(path), cookies)
+            }
+            CookieStoreLocation::Memory => (None, CookieStore::default()),
+        };
+
+        let transport = match self.transport {
+            Some(transport) => transport,
+            None => {
+                let mut headers = self.default_headers;
+                if let Some(user_agent) = self.user_agent {
+                    let user_agent = HeaderValue::from_str(&user_agent)
+                        .map_err(|_err| Error::InvalidHeaderValue)?;
+                    headers.insert(USER_AGENT, user_agent);
+                }
+
+                let mut builder = ReqwestClientBuilder::new()
+                    .redirect(RedirectPolicy::none())
+                    .use_rustls_tls()
+                    .default_headers(headers);
+                if let Some(timeout) = self.timeout {
+                    builder = builder.timeout(timeout);
+                }
+                if let Some(timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(timeout);
+                }
+                if let Some(proxy) = self.proxy {
+                    builder = builder.proxy(Proxy::all(proxy)?);
+                }
+
+                Arc::new(HttpClient::new(builder.build()?))
+            }
+        };
+        let http = Session::new(self.base_url, transport, Arc::new(Mutex::new(cookies)));
+
+        Ok(Client {
+            http,
+            cookie_store_path,
+        })
+    }
+}
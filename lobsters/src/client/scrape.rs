@@ -0,0 +1,811 @@
+//! Parsing of models from HTML pages, for parts of the site without a JSON API
+//!
+//! The HTML does not include everything the JSON API does. Fields that can't be determined are
+//! left empty or zero, see `stories_from_html` and `comments_from_html`.
+
+use std::collections::HashMap;
+
+use kuchiki::iter::NodeIterator;
+use kuchiki::traits::TendrilSink;
+use kuchiki::{ElementData, NodeDataRef, NodeRef};
+use url::Url;
+
+use crate::error::Error;
+use crate::models::{
+    Comment, CommentId, Hat, Invitation, InvitationTree, Moderation, ModerationTarget, Reply,
+    ShortTag, Story, StoryId, Timestamp, User,
+};
+
+type Element = NodeDataRef<ElementData>;
+
+/// Parse the stories listed on a page
+///
+/// `flags`, `upvotes`, `downvotes`, `description` and `comments` are not available and most
+/// `User` fields of the submitter are empty.
+pub(super) fn stories_from_html(base_url: &Url, body: &str) -> Vec<Story> {
+    let html = kuchiki::parse_html().one(body);
+    html.select("li.story")
+        .map(|stories| {
+            stories
+                .filter_map(|story| story_from_element(base_url, &story))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parse the comments listed on a page
+///
+/// `updated_at`, `flags`, `upvotes` and `downvotes` are not available, `indent_level` is always
+/// 1, and most `User` fields of the commenter are empty. `story_id` and `story_title` are filled
+/// in.
+pub(super) fn comments_from_html(base_url: &Url, body: &str) -> Vec<Comment> {
+    let html = kuchiki::parse_html().one(body);
+    html.select("div.comment[data-shortid]")
+        .map(|comments| {
+            comments
+                .filter_map(|comment| comment_from_element(base_url, &comment))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parse the titles of the stories that the comments listed on a page belong to
+pub(super) fn story_titles_from_html(body: &str) -> HashMap<StoryId, String> {
+    let html = kuchiki::parse_html().one(body);
+    html.select(".comment .byline a[href^='/s/']")
+        .map(|links| {
+            links
+                .filter_map(|link| {
+                    let href = attribute(&link, "href")?;
+                    // Skip the links to the comments themselves
+                    if href.contains('#') {
+                        return None;
+                    }
+                    Some((story_id_from_href(&href)?, text(&link)))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parse the replies listed on a replies page
+///
+/// The comments are parsed as by `comments_from_html`.
+pub(super) fn replies_from_html(base_url: &Url, body: &str) -> Vec<Reply> {
+    let html = kuchiki::parse_html().one(body);
+    html.select("div.comment[data-shortid]")
+        .map(|comments| {
+            comments
+                .filter_map(|comment| reply_from_element(base_url, &comment))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn reply_from_element(base_url: &Url, element: &Element) -> Option<Reply> {
+    let mut comment = comment_from_element(base_url, element)?;
+    let is_unread = has_class(element, "comment_unread");
+
+    // Replies to comments link to the comment they are replying to
+    let parent_comment_id = element
+        .as_node()
+        .select(".byline a")
+        .ok()?
+        .find(|link| text(link) == "parent")
+        .and_then(|link| attribute(&link, "href"))
+        .and_then(|href| comment_id_from_href(&href));
+    comment.parent_comment = parent_comment_id.clone();
+
+    Some(Reply {
+        story_id: comment.story_id.clone()?,
+        comment,
+        parent_comment_id,
+        is_unread,
+    })
+}
+
+/// Parse the tags that are checked in the form of the filters page
+///
+/// Returns `Error::MissingHtmlElement` if the page has no filters form, rather than reporting
+/// that no tags are filtered.
+pub(super) fn filtered_tags_from_html(body: &str) -> Result<Vec<ShortTag>, Error> {
+    let html = kuchiki::parse_html().one(body);
+    let form = html
+        .select_first("form[action='/filters']")
+        .map_err(|()| Error::MissingHtmlElement)?;
+    let tags = form
+        .as_node()
+        .select("input[type='checkbox'][name='tags[]'][checked]")
+        .map(|inputs| {
+            inputs
+                .filter_map(|input| attribute(&input, "value"))
+                .map(ShortTag)
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(tags)
+}
+
+/// Parse the pending invitations listed in the table of the invitations page
+pub(super) fn invitations_from_html(body: &str) -> Vec<Invitation> {
+    let html = kuchiki::parse_html().one(body);
+    html.select("table tr")
+        .map(|rows| {
+            rows.filter_map(|row| {
+                let mut cells = row.as_node().select("td").ok()?;
+                let email = text(&cells.next()?);
+                let memo = cells.next().map(|memo| text(&memo));
+                let created_at = cells.next().map(|date| text(&date)).unwrap_or_default();
+                Some(Invitation {
+                    email,
+                    memo: memo.filter(|memo| !memo.is_empty()),
+                    created_at,
+                })
+            })
+            .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Find a user in the tree of users and parse the users they invited
+///
+/// The tree is nested lists, each item links to a user and contains a list of their invitees.
+pub(super) fn invitation_tree_from_html(body: &str, username: &str) -> Option<InvitationTree> {
+    let html = kuchiki::parse_html().one(body);
+    let href = format!("/u/{}", username);
+    let item = html.select("li").ok()?.find(|item| {
+        user_link(item).and_then(|link| attribute(&link, "href")) == Some(href.clone())
+    })?;
+
+    invitation_tree_from_element(&item)
+}
+
+fn invitation_tree_from_element(item: &Element) -> Option<InvitationTree> {
+    let username = text(&user_link(item)?);
+    let invitees = item
+        .as_node()
+        .children()
+        .elements()
+        .filter(|child| &*child.name.local == "ul")
+        .flat_map(|list| list.as_node().children().elements().collect::<Vec<_>>())
+        .filter(|child| &*child.name.local == "li")
+        .filter_map(|child| invitation_tree_from_element(&child))
+        .collect();
+
+    Some(InvitationTree { username, invitees })
+}
+
+/// The link to the user an item of the user tree is for
+fn user_link(item: &Element) -> Option<Element> {
+    item.as_node()
+        .children()
+        .elements()
+        .find(|child| &*child.name.local == "a")
+}
+
+/// Parse the hats listed on the hats page, along with the users they were granted to
+pub(super) fn hats_from_html(body: &str) -> Vec<Hat> {
+    let html = kuchiki::parse_html().one(body);
+    html.select("table tr")
+        .map(|rows| rows.filter_map(|row| hat_from_row(&row)).collect())
+        .unwrap_or_default()
+}
+
+/// Parse the hats offered by the hat selector of a form
+pub(super) fn wearable_hats_from_html(body: &str) -> Vec<Hat> {
+    let html = kuchiki::parse_html().one(body);
+    html.select("select[name$='hat_id]'] option, select[name='hat_id'] option")
+        .map(|options| {
+            options
+                .filter_map(|option| {
+                    let short_id = attribute(&option, "value").filter(|id| !id.is_empty())?;
+                    Some(Hat {
+                        short_id: Some(short_id),
+                        hat: text(&option),
+                        link: None,
+                        username: None,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn hat_from_row(row: &Element) -> Option<Hat> {
+    let node = row.as_node();
+    let user = node.select_first("a[href^='/u/']").ok()?;
+    let hat = node.select_first("span.hat").ok()?;
+    let link = hat
+        .as_node()
+        .select_first("a[href]")
+        .ok()
+        .and_then(|link| attribute(&link, "href"));
+
+    Some(Hat {
+        short_id: None,
+        hat: text(&hat),
+        link,
+        username: Some(text(&user)),
+    })
+}
+
+/// Parse the entries of the moderation log
+///
+/// Each entry spans two rows of the table, the first with the date and what was moderated, the
+/// second with the moderator, action and reason. The first row of an entry has the `nobottom`
+/// class, entries that are missing a row are skipped.
+pub(super) fn moderations_from_html(base_url: &Url, body: &str) -> Vec<Moderation> {
+    let html = kuchiki::parse_html().one(body);
+    let rows = match html.select("table tr") {
+        Ok(rows) => rows,
+        Err(()) => return Vec::new(),
+    };
+
+    let mut entries: Vec<Vec<Element>> = Vec::new();
+    for row in rows.filter(|row| row.as_node().select_first("td").is_ok()) {
+        if has_class(&row, "nobottom") {
+            entries.push(vec![row]);
+        } else if let Some(entry) = entries.last_mut() {
+            entry.push(row);
+        }
+    }
+
+    entries
+        .iter()
+        .filter_map(|entry| match entry.as_slice() {
+            [target, action] => moderation_from_rows(base_url, target, action),
+            _ => None,
+        })
+        .collect()
+}
+
+fn moderation_from_rows(base_url: &Url, target: &Element, action: &Element) -> Option<Moderation> {
+    let mut target_cells = target.as_node().select("td").ok()?;
+    let created_at = text(&target_cells.next()?);
+    let target_cell = target_cells.next()?;
+    let link = target_cell.as_node().select_first("a[href]").ok();
+    let kind = link
+        .as_ref()
+        .and_then(|link| attribute(link, "href"))
+        .and_then(|href| moderation_target(&href));
+
+    let mut action_cells = action.as_node().select("td").ok()?;
+    let moderator = action_cells
+        .next()?
+        .as_node()
+        .select_first("a[href^='/u/']")
+        .ok()
+        .map(|link| text(&link));
+    let action_cell = action_cells.next()?;
+    let reason = action_cell
+        .as_node()
+        .select_first("blockquote, .reason")
+        .ok()
+        .map(|reason| {
+            text(&reason)
+                .trim_start_matches("Reason:")
+                .trim()
+                .to_string()
+        })
+        .filter(|reason| !reason.is_empty());
+
+    Some(Moderation {
+        created_at,
+        moderator,
+        target: kind,
+        target_description: text(&target_cell),
+        target_url: link.and_then(|link| href(base_url, &link)),
+        action: action_cell
+            .as_node()
+            .select_first("em")
+            .map(|action| text(&action))
+            .unwrap_or_else(|()| text(&action_cell)),
+        reason,
+    })
+}
+
+/// The kind of thing a link in the moderation log points to
+fn moderation_target(href: &str) -> Option<ModerationTarget> {
+    if comment_id_from_href(href).is_some() {
+        Some(ModerationTarget::Comment)
+    } else if href.starts_with("/s/") {
+        Some(ModerationTarget::Story)
+    } else if href.starts_with("/t/") {
+        Some(ModerationTarget::Tag)
+    } else if href.starts_with("/u/") {
+        Some(ModerationTarget::User)
+    } else {
+        None
+    }
+}
+
+fn story_from_element(base_url: &Url, story: &Element) -> Option<Story> {
+    let node = story.as_node();
+    let short_id = attribute(story, "data-shortid")?;
+    let link = node.select_first(".details .link a").ok()?;
+    let comments_url = node
+        .select_first(".byline .comments_label a")
+        .ok()
+        .and_then(|link| href(base_url, &link))
+        .unwrap_or_else(|| join(base_url, &format!("s/{}", short_id)));
+
+    // Text stories link to their comments, the JSON API gives them no URL
+    let url = attribute(&link, "href")
+        .and_then(|href| base_url.join(&href).ok())
+        .filter(|url| url.as_str() != comments_url);
+    let comment_count = node
+        .select_first(".byline .comments_label a")
+        .ok()
+        .and_then(|link| {
+            link.text_contents()
+                .split_whitespace()
+                .next()
+                .and_then(|count| count.parse().ok())
+        })
+        .unwrap_or(0);
+    let tags = node
+        .select(".tags a.tag")
+        .map(|tags| tags.map(|tag| ShortTag(text(&tag))).collect())
+        .unwrap_or_default();
+
+    Some(Story {
+        short_id_url: join(base_url, &format!("s/{}", short_id)),
+        short_id: StoryId(short_id),
+        created_at: created_at(node)?,
+        title: text(&link),
+        url,
+        score: score(node),
+        flags: 0,
+        upvotes: 0,
+        downvotes: 0,
+        comment_count,
+        description: None,
+        description_plain: None,
+        comments_url,
+        submitter_user: user(node.select_first(".byline a.u-author").ok()?),
+        user_is_author: false,
+        is_flagged: false,
+        tags,
+        comments: None,
+    })
+}
+
+fn comment_from_element(base_url: &Url, comment: &Element) -> Option<Comment> {
+    let node = comment.as_node();
+    let short_id = attribute(comment, "data-shortid")?;
+    let created_at = created_at(node)?;
+    let links: Vec<_> = node
+        .select(".byline a[href^='/s/']")
+        .map(|links| links.collect())
+        .unwrap_or_default();
+    let url = links
+        .iter()
+        .filter_map(|link| href(base_url, link))
+        .find(|url| url.contains("#c_"))
+        .unwrap_or_default();
+    let story = links
+        .iter()
+        .find(|link| attribute(link, "href").is_some_and(|href| !href.contains('#')));
+    let story_id = story
+        .and_then(|link| attribute(link, "href"))
+        .and_then(|href| story_id_from_href(&href));
+    let commenter = node
+        .select(".byline a[href^='/u/']")
+        .ok()?
+        .find(|link| !text(link).is_empty())?;
+    let comment_text = node
+        .select_first(".comment_text")
+        .map(|text| inner_html(text.as_node()))
+        .unwrap_or_default();
+
+    Some(Comment {
+        short_id_url: join(base_url, &format!("c/{}", short_id)),
+        short_id: CommentId(short_id),
+        created_at,
+        updated_at: None,
+        is_deleted: false,
+        is_moderated: false,
+        score: score(node),
+        flags: 0,
+        upvotes: 0,
+        downvotes: 0,
+        is_flagged: false,
+        parent_comment: None,
+        comment: comment_text,
+        comment_plain: None,
+        url,
+        indent_level: 1,
+        depth: None,
+        commenting_user: user(commenter),
+        hat: node.select_first(".byline span.hat").ok().map(|hat| Hat {
+            short_id: None,
+            hat: text(&hat),
+            link: None,
+            username: None,
+        }),
+        story_id,
+        story_title: story.map(text),
+    })
+}
+
+/// A user with only the username and avatar known
+fn user(link: Element) -> User {
+    let avatar_url = link
+        .as_node()
+        .parent()
+        .and_then(|parent| parent.select_first("img.avatar").ok())
+        .and_then(|img| attribute(&img, "src"))
+        .unwrap_or_default();
+
+    User {
+        avatar_url,
+        ..User::with_username(text(&link))
+    }
+}
+
+/// The creation time from the title of the byline's timestamp, in the format of the JSON API
+///
+/// For example `2019-04-21 11:07:44 -0500` becomes `2019-04-21T11:07:44-05:00`.
+fn created_at(node: &NodeRef) -> Option<Timestamp> {
+    let span = node.select_first(".byline span[title]").ok()?;
+    let title = attribute(&span, "title")?;
+    let mut parts = title.split_whitespace();
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(date), Some(time), Some(offset)) if offset.len() == 5 => Timestamp::parse(&format!(
+            "{}T{}{}:{}",
+            date,
+            time,
+            &offset[..3],
+            &offset[3..]
+        )),
+        _ => None,
+    }
+}
+
+/// The story a link like `/s/abc123/title` points to
+fn story_id_from_href(href: &str) -> Option<StoryId> {
+    href.trim_start_matches("/s/")
+        .split('/')
+        .next()
+        .filter(|short_id| !short_id.is_empty())
+        .map(|short_id| StoryId(short_id.to_string()))
+}
+
+/// The comment a link like `/s/abc123/title#c_def456` or `/c/def456` points to
+fn comment_id_from_href(href: &str) -> Option<CommentId> {
+    let short_id = match href.find("#c_") {
+        Some(index) => &href[index + 3..],
+        None if href.starts_with("/c/") => &href[3..],
+        None => return None,
+    };
+
+    if short_id.is_empty() {
+        None
+    } else {
+        Some(CommentId(short_id.to_string()))
+    }
+}
+
+fn score(node: &NodeRef) -> i32 {
+    node.select_first(".voters .score")
+        .ok()
+        .and_then(|score| text(&score).parse().ok())
+        .unwrap_or(0)
+}
+
+fn has_class(element: &Element, class: &str) -> bool {
+    attribute(element, "class")
+        .is_some_and(|classes| classes.split_whitespace().any(|c| c == class))
+}
+
+fn attribute(element: &Element, name: &str) -> Option<String> {
+    element.attributes.borrow().get(name).map(str::to_string)
+}
+
+fn href(base_url: &Url, link: &Element) -> Option<String> {
+    attribute(link, "href")
+        .and_then(|href| base_url.join(&href).ok())
+        .map(Url::into_string)
+}
+
+fn join(base_url: &Url, path: &str) -> String {
+    base_url
+        .join(path)
+        .map(Url::into_string)
+        .unwrap_or_default()
+}
+
+fn text(element: &Element) -> String {
+    element.text_contents().trim().to_string()
+}
+
+fn inner_html(node: &NodeRef) -> String {
+    node.children()
+        .map(|child| child.to_string())
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STORY_HTML: &str = r#"<html><body><ol class="stories list">
+        <li id="story_abc123" data-shortid="abc123" class="story">
+        <div class="story_liner h-entry">
+          <div class="voters"><a class="upvoter" href="/login"></a><div class="score">12</div></div>
+          <div class="details">
+            <span class="link h-cite u-repost-of">
+              <a class="u-url" href="https://example.com/post">A Story</a>
+            </span>
+            <span class="tags"><a class="tag tag_rust" href="/t/rust">rust</a></span>
+            <a class="domain" href="/domain/example.com">example.com</a>
+            <div class="byline">
+              <a href="/u/alice"><img src="/avatars/alice-16.png" class="avatar"></a>
+              <span> via </span>
+              <a class="u-author h-card" href="/u/alice">alice</a>
+              <span title="2019-04-21 11:07:44 -0500">2 hours ago</span>
+              <span> | </span>
+              <span class="comments_label"><a href="/s/abc123/a_story">4 comments</a></span>
+            </div>
+          </div>
+        </div>
+        </li>
+        <li id="story_xyz789" data-shortid="xyz789" class="story">
+        <div class="story_liner h-entry">
+          <div class="voters"><a class="upvoter" href="/login"></a><div class="score">3</div></div>
+          <div class="details">
+            <span class="link h-cite u-repost-of">
+              <a class="u-url" href="/s/xyz789/ask_a_question">Ask: A question</a>
+            </span>
+            <span class="tags"><a class="tag tag_ask" href="/t/ask">ask</a></span>
+            <div class="byline">
+              <a class="u-author h-card" href="/u/bob">bob</a>
+              <span title="2019-04-20 09:00:00 +1000">1 day ago</span>
+              <span class="comments_label"><a href="/s/xyz789/ask_a_question">no comments</a></span>
+            </div>
+          </div>
+        </div>
+        </li>
+        </ol></body></html>"#;
+
+    const COMMENT_HTML: &str = r#"<html><body><ol class="comments comments1"><li>
+        <div id="c_def456" data-shortid="def456" class="comment">
+          <div class="voters"><a class="upvoter"></a><div class="score">5</div></div>
+          <div class="details">
+            <div class="byline">
+              <a name="c_def456"></a>
+              <a href="/u/alice"><img src="/avatars/alice-16.png" class="avatar"></a>
+              <a href="/u/alice">alice</a>
+              <span title="2019-04-21 11:07:44 -0500">2 hours ago</span>
+              | <a href="/s/abc123/a_story#c_def456">link</a>
+              on: <a href="/s/abc123/a_story">A Story</a>
+            </div>
+            <div class="comment_text"><p>Hello <em>there</em></p></div>
+          </div>
+        </div>
+        </li></ol></body></html>"#;
+
+    const REPLY_HTML: &str = r#"<html><body><ol class="comments comments1"><li>
+        <div id="c_ghi789" data-shortid="ghi789" class="comment comment_unread">
+          <div class="voters"><a class="upvoter"></a><div class="score">1</div></div>
+          <div class="details">
+            <div class="byline">
+              <a href="/u/bob">bob</a>
+              <span title="2019-04-22 08:00:00 -0500">1 hour ago</span>
+              | <a href="/s/abc123/a_story#c_ghi789">link</a>
+              | <a href="/s/abc123/a_story#c_def456">parent</a>
+              on: <a href="/s/abc123/a_story">A Story</a>
+            </div>
+            <div class="comment_text"><p>Thanks!</p></div>
+          </div>
+        </div>
+        </li></ol></body></html>"#;
+
+    const HATS_HTML: &str = r#"<html><body><table class="data zebra">
+        <tr><th>User</th><th>Hat</th></tr>
+        <tr><td><a href="/u/alice">alice</a></td>
+        <td><span class="hat hat_sysop" title="Granted by bob on 2019-01-01">
+        <span class="crown"><a href="https://example.com/" target="_blank">Sysop</a></span>
+        </span></td></tr>
+        <tr><td><a href="/u/bob">bob</a></td>
+        <td><span class="hat hat_moderator" title="Granted by carol on 2018-01-01">
+        <span class="crown">Moderator</span></span></td></tr>
+        </table></body></html>"#;
+
+    const MODERATIONS_HTML: &str = r#"<html><body><table class="data">
+        <tr><th>Date/Moderator</th><th>Story/Comment/User, Action, Reason</th></tr>
+        <tr class="nobottom"><td class="nowrap">2019-04-21 11:07</td>
+        <td><a href="/s/abc123/a_story">Story: A story</a></td></tr>
+        <tr><td class="nowrap"><a href="/u/alice">alice</a></td>
+        <td><em>deleted story</em><blockquote>Reason: Off-topic</blockquote></td></tr>
+        <tr class="nobottom"><td class="nowrap">2019-04-20 09:00</td>
+        <td><a href="/s/abc123/a_story#c_def456">Comment by bob on A story</a></td></tr>
+        <tr><td class="nowrap"></td><td><em>edited comment</em></td></tr>
+        </table></body></html>"#;
+
+    fn base_url() -> Url {
+        Url::parse(crate::URL).unwrap()
+    }
+
+    #[test]
+    fn parse_stories() {
+        let stories = stories_from_html(&base_url(), STORY_HTML);
+        assert_eq!(stories.len(), 2);
+
+        let story = &stories[0];
+        assert_eq!(story.short_id, StoryId("abc123".to_string()));
+        assert_eq!(story.title, "A Story");
+        assert_eq!(
+            story.url.as_ref().map(Url::as_str),
+            Some("https://example.com/post")
+        );
+        assert_eq!(story.comments_url, "https://lobste.rs/s/abc123/a_story");
+        assert_eq!(story.created_at.as_str(), "2019-04-21T11:07:44-05:00");
+        assert_eq!(story.score, 12);
+        assert_eq!(story.comment_count, 4);
+        assert_eq!(story.tags, vec![ShortTag("rust".to_string())]);
+        assert_eq!(story.submitter_user.username, "alice");
+        assert_eq!(story.submitter_user.avatar_url, "/avatars/alice-16.png");
+
+        // Text stories have no URL
+        assert_eq!(stories[1].url, None);
+        assert_eq!(stories[1].comment_count, 0);
+    }
+
+    #[test]
+    fn parse_comments() {
+        let comments = comments_from_html(&base_url(), COMMENT_HTML);
+        assert_eq!(comments.len(), 1);
+
+        let comment = &comments[0];
+        assert_eq!(comment.short_id, CommentId("def456".to_string()));
+        assert_eq!(comment.score, 5);
+        assert_eq!(comment.comment, "<p>Hello <em>there</em></p>");
+        assert_eq!(comment.url, "https://lobste.rs/s/abc123/a_story#c_def456");
+        assert_eq!(comment.commenting_user.username, "alice");
+        assert_eq!(comment.story_id, Some(StoryId("abc123".to_string())));
+        assert_eq!(comment.story_title.as_deref(), Some("A Story"));
+    }
+
+    #[test]
+    fn parse_replies() {
+        let replies = replies_from_html(&base_url(), REPLY_HTML);
+        assert_eq!(replies.len(), 1);
+
+        let reply = &replies[0];
+        assert_eq!(reply.comment.short_id, CommentId("ghi789".to_string()));
+        assert_eq!(reply.story_id, StoryId("abc123".to_string()));
+        assert_eq!(
+            reply.parent_comment_id,
+            Some(CommentId("def456".to_string()))
+        );
+        assert!(reply.is_unread);
+
+        // Comments on other pages aren't unread replies to anything
+        let replies = replies_from_html(&base_url(), COMMENT_HTML);
+        assert_eq!(replies[0].parent_comment_id, None);
+        assert!(!replies[0].is_unread);
+    }
+
+    #[test]
+    fn parse_hats() {
+        let hats = hats_from_html(HATS_HTML);
+        assert_eq!(hats.len(), 2);
+        assert_eq!(hats[0].hat, "Sysop");
+        assert_eq!(hats[0].username.as_deref(), Some("alice"));
+        assert_eq!(hats[0].link.as_deref(), Some("https://example.com/"));
+        assert_eq!(hats[1].link, None);
+    }
+
+    #[test]
+    fn parse_wearable_hats() {
+        let html = r#"<html><body><form>
+            <select name="message[hat_id]"><option value="">Put on hat</option>
+            <option value="h4t1d">Sysop</option></select>
+            </form></body></html>"#;
+        let hats = wearable_hats_from_html(html);
+        assert_eq!(hats.len(), 1);
+        assert_eq!(hats[0].short_id.as_deref(), Some("h4t1d"));
+        assert_eq!(hats[0].hat, "Sysop");
+    }
+
+    #[test]
+    fn parse_moderations() {
+        let moderations = moderations_from_html(&base_url(), MODERATIONS_HTML);
+        assert_eq!(moderations.len(), 2);
+
+        let deleted = &moderations[0];
+        assert_eq!(deleted.created_at, "2019-04-21 11:07");
+        assert_eq!(deleted.moderator.as_deref(), Some("alice"));
+        assert_eq!(deleted.target, Some(ModerationTarget::Story));
+        assert_eq!(deleted.target_description, "Story: A story");
+        assert_eq!(
+            deleted.target_url.as_deref(),
+            Some("https://lobste.rs/s/abc123/a_story")
+        );
+        assert_eq!(deleted.action, "deleted story");
+        assert_eq!(deleted.reason.as_deref(), Some("Off-topic"));
+
+        let edited = &moderations[1];
+        assert_eq!(edited.moderator, None);
+        assert_eq!(edited.target, Some(ModerationTarget::Comment));
+        assert_eq!(edited.reason, None);
+    }
+
+    #[test]
+    fn parse_moderations_missing_row() {
+        let html = r#"<html><body><table class="data">
+            <tr class="nobottom"><td class="nowrap">2019-04-22 08:00</td>
+            <td><a href="/u/carol">User carol</a></td></tr>
+            <tr class="nobottom"><td class="nowrap">2019-04-21 11:07</td>
+            <td><a href="/s/abc123/a_story">Story: A story</a></td></tr>
+            <tr><td class="nowrap"><a href="/u/alice">alice</a></td>
+            <td><em>deleted story</em></td></tr>
+            </table></body></html>"#;
+        let moderations = moderations_from_html(&base_url(), html);
+        assert_eq!(moderations.len(), 1);
+        assert_eq!(moderations[0].created_at, "2019-04-21 11:07");
+        assert_eq!(moderations[0].action, "deleted story");
+    }
+
+    #[test]
+    fn parse_filtered_tags() {
+        let html = r#"<html><body><form action="/filters" method="post">
+            <input type="checkbox" name="tags[]" id="tags_" value="java" />
+            <input type="checkbox" name="tags[]" id="tags_" value="php" checked="checked" />
+            <input type="checkbox" name="tags[]" id="tags_" value="rust" />
+            </form></body></html>"#;
+        let tags = filtered_tags_from_html(html).unwrap();
+        assert_eq!(tags, vec![ShortTag("php".to_string())]);
+    }
+
+    #[test]
+    fn parse_filtered_tags_without_form() {
+        let html = r#"<html><body><p>Something went wrong</p></body></html>"#;
+        match filtered_tags_from_html(html) {
+            Err(Error::MissingHtmlElement) => (),
+            other => panic!("Expected Error::MissingHtmlElement got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_invitations() {
+        let html = r#"<html><body><table class="data">
+            <tr><th>E-mail</th><th>Memo</th><th>Sent</th></tr>
+            <tr><td>carol@example.com</td><td>From the team chat</td><td>2019-04-21</td></tr>
+            <tr><td>dave@example.com</td><td></td><td>2019-04-20</td></tr>
+            </table></body></html>"#;
+        let invitations = invitations_from_html(html);
+        assert_eq!(invitations.len(), 2);
+        assert_eq!(invitations[0].email, "carol@example.com");
+        assert_eq!(invitations[0].memo.as_deref(), Some("From the team chat"));
+        assert_eq!(invitations[0].created_at, "2019-04-21");
+        assert_eq!(invitations[1].memo, None);
+    }
+
+    #[test]
+    fn parse_invitation_tree() {
+        let html = r#"<html><body><ul class="user_tree">
+            <li><a href="/u/alice">alice</a> (100)
+              <ul class="user_tree">
+                <li><a href="/u/bob">bob</a> (10)
+                  <ul class="user_tree"><li><a href="/u/dave">dave</a> (1)</li></ul>
+                </li>
+                <li><a href="/u/carol">carol</a> (5)</li>
+              </ul>
+            </li>
+            </ul></body></html>"#;
+
+        let tree = invitation_tree_from_html(html, "bob").expect("bob not found");
+        assert_eq!(tree.username, "bob");
+        assert_eq!(tree.invitees.len(), 1);
+        assert_eq!(tree.invitees[0].username, "dave");
+
+        let tree = invitation_tree_from_html(html, "alice").expect("alice not found");
+        let invitees: Vec<_> = tree.invitees.iter().map(|user| &user.username).collect();
+        assert_eq!(invitees, vec!["bob", "carol"]);
+        assert!(invitation_tree_from_html(html, "eve").is_none());
+    }
+}
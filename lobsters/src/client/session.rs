@@ -0,0 +1,141 @@
+use std::fs::File;
+use std::sync::{Arc, Mutex};
+
+use cookie_store::CookieStore;
+use futures::{Future, IntoFuture};
+use log::info;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, COOKIE, SET_COOKIE};
+use serde::Serialize;
+use url::Url;
+
+use crate::error::Error;
+
+use super::transport::{Method, Request, Response, Transport};
+
+/// Sends requests relative to the base URL through a transport, maintaining the cookie store
+#[derive(Clone)]
+pub(super) struct Session {
+    base_url: Url,
+    transport: Arc<dyn Transport>,
+    cookies: Arc<Mutex<CookieStore>>,
+}
+
+impl Session {
+    pub(super) fn new(
+        base_url: Url,
+        transport: Arc<dyn Transport>,
+        cookies: Arc<Mutex<CookieStore>>,
+    ) -> Self {
+        Session {
+            base_url,
+            transport,
+            cookies,
+        }
+    }
+
+    pub(super) fn post<B>(
+        &self,
+        path: &str,
+        body: B,
+        csrf_token: String,
+    ) -> impl Future<Item = Response, Error = Error>
+    where
+        B: Serialize,
+    {
+        let body = serde_urlencoded::to_string(body).map_err(|_err| Error::FormEncoding);
+        let request = self.request(Method::Post, path).and_then(|mut request| {
+            let token =
+                HeaderValue::from_str(&csrf_token).map_err(|_err| Error::InvalidHeaderValue)?;
+            request.headers.insert("X-CSRF-Token", token);
+            request.body = Some(body?);
+            Ok(request)
+        });
+
+        self.send(request)
+    }
+
+    pub(super) fn get(&self, path: &str) -> impl Future<Item = Response, Error = Error> {
+        self.send(self.request(Method::Get, path))
+    }
+
+    pub(super) fn get_json(&self, path: &str) -> impl Future<Item = Response, Error = Error> {
+        let request = self.request(Method::Get, path).map(|mut request| {
+            request
+                .headers
+                .insert(ACCEPT, HeaderValue::from_static("application/json"));
+            request
+        });
+
+        self.send(request)
+    }
+
+    pub(super) fn save_cookies(&self, file: &mut File) -> Result<(), Error> {
+        self.cookies
+            .lock()
+            .unwrap()
+            .save_json(file)
+            .map_err(|_err| Error::CookieStore)
+    }
+
+    pub(super) fn clear_cookies(&self) {
+        *self.cookies.lock().unwrap() = CookieStore::default();
+    }
+
+    pub(super) fn base_url(&self) -> &Url {
+        &self.base_url
+    }
+
+    fn request(&self, method: Method, path: &str) -> Result<Request, Error> {
+        let url = self.base_url.join(path)?;
+        let headers = Self::cookie_headers(&self.cookies, &url);
+
+        Ok(Request {
+            method,
+            url,
+            headers,
+            body: None,
+        })
+    }
+
+    fn send(&self, request: Result<Request, Error>) -> impl Future<Item = Response, Error = Error> {
+        let transport = self.transport.clone();
+        let cookies = self.cookies.clone();
+
+        request
+            .into_future()
+            .and_then(move |request| {
+                match request.method {
+                    Method::Get => info!("GET {}", request.url.as_str()),
+                    Method::Post => info!("POST {}", request.url.as_str()),
+                }
+
+                transport.send(request)
+            })
+            .map(move |res| Self::store_cookies(res, &cookies))
+    }
+
+    fn cookie_headers(cookies: &Mutex<CookieStore>, url: &Url) -> HeaderMap {
+        // Add cookies to request
+        let store = cookies.lock().unwrap();
+        let cookies = store.matches(url);
+
+        cookies
+            .iter()
+            .fold(HeaderMap::new(), |mut headers, cookie| {
+                // NOTE(unwrap): Assumed to be safe since it was valid when put into the store
+                headers.append(COOKIE, cookie.encoded().to_string().parse().unwrap());
+                headers
+            })
+    }
+
+    fn store_cookies(res: Response, cookies: &Mutex<CookieStore>) -> Response {
+        res.headers.get_all(SET_COOKIE).iter().for_each(|cookie| {
+            cookie
+                .to_str()
+                .ok()
+                .and_then(|cookie| cookies.lock().unwrap().parse(cookie, &res.url).ok());
+        });
+
+        res
+    }
+}
@@ -0,0 +1,218 @@
+//! Pluggable HTTP transports
+//!
+//! A `Client` sends all of its requests through a `Transport`. By default this is a `reqwest`
+//! client but any implementation can be supplied with `ClientBuilder::transport`.
+//! `FakeTransport` returns canned responses, allowing the client to be tested without a
+//! network:
+//!
+//! ```
+//! use futures::Future;
+//! use lobsters::client::transport::{FakeTransport, Method, Response, StatusCode};
+//! use lobsters::{ClientBuilder, url::Url};
+//!
+//! let base_url = Url::parse(lobsters::URL).unwrap();
+//! let transport = FakeTransport::new();
+//! transport.on(
+//!     Method::Get,
+//!     "/tags",
+//!     Response::new(StatusCode::OK, base_url.join("tags").unwrap()).body("[]"),
+//! );
+//!
+//! let client = ClientBuilder::new(base_url)
+//!     .in_memory_cookie_store()
+//!     .transport(transport.clone())
+//!     .build()
+//!     .unwrap();
+//!
+//! assert!(client.tags().wait().unwrap().is_empty());
+//! assert_eq!(transport.requests()[0].url.path(), "/tags");
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use futures::Future;
+use reqwest::header::{HeaderMap, HeaderValue, IntoHeaderName, LOCATION};
+use serde::de::DeserializeOwned;
+use url::Url;
+
+use crate::error::Error;
+
+pub use reqwest::StatusCode;
+
+/// The HTTP method of a request
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Method {
+    /// GET
+    Get,
+    /// POST
+    Post,
+}
+
+/// A HTTP request to be sent by a `Transport`
+#[derive(Clone, Debug)]
+pub struct Request {
+    /// The request method
+    pub method: Method,
+    /// The absolute URL of the request
+    pub url: Url,
+    /// Request headers, including cookies
+    pub headers: HeaderMap,
+    /// Form encoded request body
+    pub body: Option<String>,
+}
+
+/// A HTTP response with its body read into memory
+#[derive(Clone, Debug)]
+pub struct Response {
+    /// The response status code
+    pub status: StatusCode,
+    /// The URL of the request that produced this response
+    pub url: Url,
+    /// Response headers
+    pub headers: HeaderMap,
+    /// The response body
+    pub body: Vec<u8>,
+}
+
+/// The future returned by `Transport::send`
+pub type ResponseFuture = Box<dyn Future<Item = Response, Error = Error> + Send>;
+
+/// Sends HTTP requests on behalf of a `Client`
+///
+/// Redirects must not be followed, the client inspects them to determine the outcome of
+/// requests.
+pub trait Transport: Send + Sync {
+    /// Send `request` and read the response
+    fn send(&self, request: Request) -> ResponseFuture;
+}
+
+/// A transport that returns canned responses and records the requests it receives
+///
+/// Responses are registered per method and path, and are returned in the order they were
+/// registered. A request with no remaining response receives a 404. Clones share the same
+/// responses and recorded requests.
+#[derive(Clone, Default)]
+pub struct FakeTransport {
+    state: Arc<Mutex<FakeState>>,
+}
+
+#[derive(Default)]
+struct FakeState {
+    responses: HashMap<(Method, String), VecDeque<Response>>,
+    requests: Vec<Request>,
+}
+
+impl Response {
+    /// Create a new response with no headers and an empty body
+    pub fn new(status: StatusCode, url: Url) -> Self {
+        Response {
+            status,
+            url,
+            headers: HeaderMap::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Add a header to the response
+    ///
+    /// Panics if `value` is not a valid header value.
+    pub fn header<K: IntoHeaderName>(mut self, name: K, value: &str) -> Self {
+        let value = HeaderValue::from_str(value).expect("invalid header value");
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Set the body of the response
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// The URL this response redirects to, if it is a redirect
+    pub fn location(&self) -> Option<Url> {
+        if !self.status.is_redirection() {
+            return None;
+        }
+
+        self.headers
+            .get(LOCATION)
+            .and_then(|header| header.to_str().ok())
+            .and_then(|location| self.url.join(location).ok())
+    }
+
+    /// The body of the response as a string
+    pub fn text(&self) -> Result<&str, Error> {
+        std::str::from_utf8(&self.body).map_err(|_err| Error::InvalidStr)
+    }
+
+    /// Deserialize the body of the response from JSON
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        serde_json::from_slice(&self.body).map_err(Error::from)
+    }
+}
+
+impl FakeTransport {
+    /// Create a new transport with no responses
+    pub fn new() -> Self {
+        FakeTransport::default()
+    }
+
+    /// Respond to the next `method` request for `path` with `response`
+    ///
+    /// `path` is matched against the path and query string of the request URL, for example
+    /// `/page/2` or `/search?q=rust`.
+    pub fn on(&self, method: Method, path: &str, response: Response) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .responses
+            .entry((method, path.to_string()))
+            .or_default()
+            .push_back(response);
+    }
+
+    /// The requests received so far, in the order they were sent
+    pub fn requests(&self) -> Vec<Request> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+impl Transport for FakeTransport {
+    fn send(&self, request: Request) -> ResponseFuture {
+        let mut state = self.state.lock().unwrap();
+        let key = match request.url.query() {
+            Some(query) => format!("{}?{}", request.url.path(), query),
+            None => request.url.path().to_string(),
+        };
+        let response = state
+            .responses
+            .get_mut(&(request.method, key))
+            .and_then(VecDeque::pop_front)
+            .unwrap_or_else(|| Response::new(StatusCode::NOT_FOUND, request.url.clone()));
+        state.requests.push(request);
+
+        Box::new(futures::future::ok(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn location_resolves_relative_urls() {
+        let url = Url::parse("https://example.com/login").unwrap();
+        let res = Response::new(StatusCode::FOUND, url).header(LOCATION, "/login/2fa");
+        assert_eq!(
+            res.location(),
+            Some(Url::parse("https://example.com/login/2fa").unwrap())
+        );
+    }
+
+    #[test]
+    fn location_ignored_unless_redirect() {
+        let url = Url::parse("https://example.com/login").unwrap();
+        let res = Response::new(StatusCode::OK, url).header(LOCATION, "/login/2fa");
+        assert_eq!(res.location(), None);
+    }
+}
@@ -9,6 +9,10 @@ pub enum Error {
     Http(reqwest::Error),
     /// An attempt was made to convert data into a string that was not valid UTF-8
     InvalidStr,
+    /// A response could not be deserialized from JSON
+    Json(serde_json::Error),
+    /// A request body could not be form encoded
+    FormEncoding,
     /// An I/O error
     Io(io::Error),
     /// An attempt to parse a string that was not a valid URL
@@ -21,6 +25,20 @@ pub enum Error {
     MissingHtmlElement,
     /// The request was not authorised or login attemp failed
     Authorisation,
+    /// The server rejected the request, the message it responded with is included
+    Rejected(String),
+    /// The server rejected a submitted form, the problems it reported are included
+    Validation(Vec<String>),
+    /// Too many requests have been made recently, try again later
+    RateLimited,
+    /// The requested item could not be found
+    NotFound,
+    /// A profile name was empty or contained characters that are not allowed in a file name
+    InvalidProfile(String),
+    /// A value supplied for a HTTP header contained invalid characters
+    InvalidHeaderValue,
+    /// Stories were requested by tag without giving any tags
+    NoTags,
 }
 
 impl From<reqwest::Error> for Error {
@@ -29,6 +47,12 @@ impl From<reqwest::Error> for Error {
     }
 }
 
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
 impl From<url::ParseError> for Error {
     fn from(error: url::ParseError) -> Self {
         Error::Url(error)
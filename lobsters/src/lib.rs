@@ -16,10 +16,24 @@
 //!
 //! This client crate allows the following actions to be performed:
 //!
-//! * Fetch stories
-//! * Fetch comments on stories
-//! * Post comments and replies
-//! * Login
+//! * Fetch stories from the front page, other feeds, tags and domains
+//! * Stream stories across pages of a feed
+//! * Submit stories
+//! * Fetch comments on stories, and the newest comments across the site
+//! * Arrange the comments of a story into a tree of replies
+//! * Post, edit and delete comments and replies
+//! * Vote on and flag stories and comments
+//! * Save and hide stories
+//! * Login, including two-factor authentication
+//! * Logout and determine the logged in user
+//! * Fetch user profiles, and the stories and comments of a user
+//! * Search stories and comments
+//! * Read, send and delete private messages
+//! * Fetch replies to your comments and stories
+//! * List hats, and wear one when commenting or messaging
+//! * Read the moderation log
+//! * Read and change the filtered tags
+//! * Send invitations, and list pending invitations and the users you invited
 //!
 //! Why
 //! ---
@@ -34,11 +48,6 @@
 //!
 //! See [lobsters-cli] for an example of using this client.
 //!
-//! Known Limitations
-//! -----------------
-//!
-//! * Support for 2FA login is not implemented
-//!
 //! Testing
 //! -------
 //!
@@ -100,8 +109,9 @@ pub mod client;
 pub mod error;
 pub mod models;
 
-pub use client::Client;
+pub use client::{Client, ClientBuilder};
 pub use error::Error;
+pub use reqwest::header;
 pub use url;
 
 /// URL of lobste.rs. Useful as `base_url` to `Client`
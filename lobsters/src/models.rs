@@ -2,60 +2,222 @@
 
 #![allow(missing_docs)]
 
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
 use serde::{Deserialize, Serialize};
+use url::Url;
 
-#[derive(Debug, Deserialize)]
+mod comment_tree;
+mod timestamp;
+
+pub use comment_tree::{CommentTree, Iter};
+pub use timestamp::Timestamp;
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct User {
     pub username: String,
-    pub created_at: String,
+    /// `None` for users known only by their username, such as the commenters of a story
+    #[serde(default)]
+    pub created_at: Option<Timestamp>,
+    #[serde(default, deserialize_with = "nullable")]
     pub is_admin: bool,
     pub about: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
     pub is_moderator: bool,
     pub karma: Option<i32>,
+    #[serde(default, deserialize_with = "nullable")]
     pub avatar_url: String,
     pub invited_by_user: Option<String>,
     pub github_username: Option<String>,
+    pub mastodon_username: Option<String>,
+    // Only included in profiles, not when embedded in stories and comments
+    pub homepage: Option<String>,
+    pub twitter_username: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub keybase_signatures: Vec<KeybaseSignature>,
 }
 
+/// A Keybase proof linked to a user's profile
 #[derive(Debug, Deserialize, Serialize)]
+pub struct KeybaseSignature {
+    pub kb_username: String,
+    pub sig_hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct StoryId(pub String);
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Story {
     pub short_id: StoryId,
+    #[serde(default, deserialize_with = "nullable")]
     pub short_id_url: String,
-    pub created_at: String,
+    pub created_at: Timestamp,
+    #[serde(default, deserialize_with = "nullable")]
     pub title: String,
-    pub url: String,
+    /// The URL of the story, `None` for text stories
+    #[serde(
+        default,
+        deserialize_with = "optional_url",
+        serialize_with = "serialize_optional_url"
+    )]
+    pub url: Option<Url>,
+    #[serde(default, deserialize_with = "nullable")]
     pub score: i32,
+    #[serde(default, deserialize_with = "nullable")]
+    pub flags: u32,
+    /// Only sent by older versions of the site, 0 otherwise
+    #[serde(default, deserialize_with = "nullable")]
     pub upvotes: u32,
+    /// Only sent by older versions of the site, 0 otherwise
+    #[serde(default, deserialize_with = "nullable")]
     pub downvotes: u32,
+    #[serde(default, deserialize_with = "nullable")]
     pub comment_count: u32,
+    /// The description as HTML
     pub description: Option<String>,
+    /// The description as the Markdown it was written in
+    pub description_plain: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
     pub comments_url: String,
+    /// Newer versions of the site send only the username, leaving the other fields empty
+    #[serde(deserialize_with = "user_or_username")]
     pub submitter_user: User,
+    /// The submitter is the author of the story
+    #[serde(default, deserialize_with = "nullable")]
+    pub user_is_author: bool,
+    #[serde(default, deserialize_with = "nullable")]
+    pub is_flagged: bool,
+    #[serde(default, deserialize_with = "nullable")]
     pub tags: Vec<ShortTag>,
     pub comments: Option<Vec<Comment>>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct CommentId(pub String);
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Comment {
     pub short_id: CommentId,
+    #[serde(default, deserialize_with = "nullable")]
     pub short_id_url: String,
-    pub created_at: String,
-    pub updated_at: String,
+    pub created_at: Timestamp,
+    /// When the comment was last edited, sent as `last_edited_at` by newer versions of the site
+    #[serde(default, alias = "last_edited_at")]
+    pub updated_at: Option<Timestamp>,
+    #[serde(default, deserialize_with = "nullable")]
     pub is_deleted: bool,
+    #[serde(default, deserialize_with = "nullable")]
     pub is_moderated: bool,
+    #[serde(default, deserialize_with = "nullable")]
     pub score: i32,
+    #[serde(default, deserialize_with = "nullable")]
+    pub flags: u32,
+    /// Only sent by older versions of the site, 0 otherwise
+    #[serde(default, deserialize_with = "nullable")]
     pub upvotes: u32,
+    /// Only sent by older versions of the site, 0 otherwise
+    #[serde(default, deserialize_with = "nullable")]
     pub downvotes: u32,
+    #[serde(default, deserialize_with = "nullable")]
+    pub is_flagged: bool,
+    /// The comment being replied to, `None` for replies to the story
+    pub parent_comment: Option<CommentId>,
+    /// The comment as HTML
+    #[serde(default, deserialize_with = "nullable")]
     pub comment: String,
+    /// The comment as the Markdown it was written in
+    pub comment_plain: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
     pub url: String,
+    /// The nesting of the comment counting from 1, sent by older versions of the site
+    ///
+    /// Use `Comment::nesting_level` to get the nesting from whichever of this and `depth` was sent.
+    #[serde(default, deserialize_with = "nullable")]
     pub indent_level: u32,
+    /// The nesting of the comment counting from 0, sent by newer versions of the site
+    pub depth: Option<u32>,
+    /// Newer versions of the site send only the username, leaving the other fields empty
+    #[serde(deserialize_with = "user_or_username")]
     pub commenting_user: User,
+    /// The hat worn by the commenter
+    pub hat: Option<Hat>,
+    /// The story the comment was posted on, only included by `Client::recent_comments`
+    pub story_id: Option<StoryId>,
+    pub story_title: Option<String>,
+}
+
+/// A hat, worn by users speaking in an official capacity
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Hat {
+    /// Identifies the hat when wearing it, only known for the hats of the logged in user
+    pub short_id: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub hat: String,
+    pub link: Option<String>,
+    /// The user the hat was granted to, only included in the list of all hats
+    pub username: Option<String>,
+}
+
+/// A comment replying to a story or comment of the logged in user
+#[derive(Debug, Serialize)]
+pub struct Reply {
+    pub comment: Comment,
+    /// The story the reply was posted on
+    pub story_id: StoryId,
+    /// The comment being replied to, `None` for replies to a story
+    pub parent_comment_id: Option<CommentId>,
+    pub is_unread: bool,
+}
+
+/// An invitation to join the site that hasn't been accepted yet
+#[derive(Debug, Clone, Serialize)]
+pub struct Invitation {
+    pub email: String,
+    pub memo: Option<String>,
+    /// When the invitation was sent, as shown on the site
+    pub created_at: String,
+}
+
+/// An invitation to send
+#[derive(Debug, Serialize)]
+pub struct NewInvitation {
+    pub email: String,
+    /// A note to the recipient, included in the invitation email
+    pub memo: String,
+}
+
+/// A user and the users they invited to the site, recursively
+#[derive(Debug, Clone, Serialize)]
+pub struct InvitationTree {
+    pub username: String,
+    pub invitees: Vec<InvitationTree>,
+}
+
+/// An entry in the moderation log
+#[derive(Debug, Serialize)]
+pub struct Moderation {
+    /// When the action was taken, as shown in the log
+    pub created_at: String,
+    /// The moderator that took the action, `None` for automatic actions
+    pub moderator: Option<String>,
+    /// The kind of thing that was moderated, `None` if it's not one of the known kinds
+    pub target: Option<ModerationTarget>,
+    /// A description of what was moderated, such as the title of a story
+    pub target_description: String,
+    pub target_url: Option<String>,
+    pub action: String,
+    pub reason: Option<String>,
+}
+
+/// The kinds of thing that moderators act on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationTarget {
+    Story,
+    Comment,
+    Tag,
+    User,
 }
 
 #[derive(Debug, Serialize)]
@@ -66,19 +228,380 @@ pub struct NewComment {
     pub parent_comment_short_id: Option<CommentId>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct ShortTag(pub String);
+#[derive(Debug, Serialize)]
+pub struct NewStory {
+    /// The URL of the story, may be omitted for text posts
+    pub url: Option<String>,
+    pub title: String,
+    /// The text of the story, or a description of the URL
+    pub description: Option<String>,
+    pub tags: Vec<ShortTag>,
+    /// The submitter is the author of the story
+    pub user_is_author: bool,
+}
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct MessageId(pub String);
+
+/// A private message
 #[derive(Debug, Deserialize, Serialize)]
+pub struct Message {
+    pub short_id: MessageId,
+    pub created_at: Timestamp,
+    #[serde(default, deserialize_with = "nullable")]
+    pub has_been_read: bool,
+    #[serde(default, deserialize_with = "nullable")]
+    pub subject: String,
+    #[serde(default, deserialize_with = "nullable")]
+    pub body: String,
+    #[serde(default, deserialize_with = "nullable")]
+    pub deleted_by_author: bool,
+    #[serde(default, deserialize_with = "nullable")]
+    pub deleted_by_recipient: bool,
+    /// `None` for messages sent by the system
+    pub author_username: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub recipient_username: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NewMessage {
+    pub recipient_username: String,
+    pub subject: String,
+    pub body: String,
+    pub hat_id: Option<String>,
+}
+
+/// Reasons a story can be flagged for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum StoryFlagReason {
+    OffTopic,
+    AlreadyPosted,
+    BrokenLink,
+    Spam,
+}
+
+/// Reasons a comment can be flagged for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CommentFlagReason {
+    OffTopic,
+    Incorrect,
+    MeToo,
+    Troll,
+    Unkind,
+    Spam,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct ShortTag(pub String);
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct TagId(pub u32);
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Tag {
-    pub id: TagId,
+    /// Only sent by older versions of the site
+    pub id: Option<TagId>,
     pub tag: String,
     pub description: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
     pub privileged: bool,
+    #[serde(default, deserialize_with = "nullable")]
     pub is_media: bool,
+    /// Sent by older versions of the site, use `Tag::is_active` instead
+    #[serde(default, deserialize_with = "nullable")]
     pub inactive: bool,
+    /// Sent in place of `inactive` by newer versions of the site
+    pub active: Option<bool>,
+    #[serde(default, deserialize_with = "nullable")]
     pub hotness_mod: f64,
+    pub category: Option<String>,
+}
+
+impl User {
+    /// A user with only the username known
+    pub(crate) fn with_username(username: String) -> Self {
+        User {
+            username,
+            created_at: None,
+            is_admin: false,
+            about: None,
+            is_moderator: false,
+            karma: None,
+            avatar_url: String::new(),
+            invited_by_user: None,
+            github_username: None,
+            mastodon_username: None,
+            homepage: None,
+            twitter_username: None,
+            keybase_signatures: Vec::new(),
+        }
+    }
+}
+
+impl Comment {
+    /// How deeply the comment is nested, 1 for comments replying to the story
+    pub fn nesting_level(&self) -> u32 {
+        match self.depth {
+            Some(depth) => depth + 1,
+            None => self.indent_level.max(1),
+        }
+    }
+}
+
+impl Tag {
+    /// Whether stories can still be tagged with this tag
+    pub fn is_active(&self) -> bool {
+        self.active.unwrap_or(!self.inactive)
+    }
+}
+
+impl StoryFlagReason {
+    /// The code the site uses for this reason
+    pub fn code(self) -> &'static str {
+        match self {
+            StoryFlagReason::OffTopic => "O",
+            StoryFlagReason::AlreadyPosted => "A",
+            StoryFlagReason::BrokenLink => "B",
+            StoryFlagReason::Spam => "S",
+        }
+    }
+}
+
+impl CommentFlagReason {
+    /// The code the site uses for this reason
+    pub fn code(self) -> &'static str {
+        match self {
+            CommentFlagReason::OffTopic => "O",
+            CommentFlagReason::Incorrect => "I",
+            CommentFlagReason::MeToo => "M",
+            CommentFlagReason::Troll => "T",
+            CommentFlagReason::Unkind => "U",
+            CommentFlagReason::Spam => "S",
+        }
+    }
+}
+
+/// Deserialize a field that may be null, using its default value in place of null
+fn nullable<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Deserialize a user that may be sent as just their username
+fn user_or_username<'de, D>(deserializer: D) -> Result<User, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum UserOrUsername {
+        User(Box<User>),
+        Username(String),
+    }
+
+    match UserOrUsername::deserialize(deserializer)? {
+        UserOrUsername::User(user) => Ok(*user),
+        UserOrUsername::Username(username) => Ok(User::with_username(username)),
+    }
+}
+
+/// Deserialize a URL that may be missing, which the site represents with an empty string
+fn optional_url<'de, D>(deserializer: D) -> Result<Option<Url>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(ref url) if !url.is_empty() => Url::parse(url).map(Some).map_err(de::Error::custom),
+        _ => Ok(None),
+    }
+}
+
+/// Serialize a URL that may be missing the way the site does, with an empty string for `None`
+fn serialize_optional_url<S>(url: &Option<Url>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(url.as_ref().map_or("", Url::as_str))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use serde::de::DeserializeOwned;
+
+    use super::*;
+
+    fn fixture<T: DeserializeOwned + Serialize>(json: &str) -> T {
+        let model: T = serde_json::from_str(json).unwrap();
+
+        // Serializing and deserializing again must not lose anything
+        let reserialized = serde_json::to_value(&model).unwrap();
+        let roundtrip: T = serde_json::from_value(reserialized.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&roundtrip).unwrap(), reserialized);
+
+        model
+    }
+
+    /// Deserialize a response saved from the site by `tests/fixtures/captured/capture.sh`
+    ///
+    /// Panics if the response hasn't been saved.
+    fn captured<T: DeserializeOwned + Serialize>(name: &str) -> T {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/captured")
+            .join(name);
+        match fs::read_to_string(&path) {
+            Ok(json) => fixture(&json),
+            Err(err) => panic!(
+                "unable to read {}, save it with capture.sh: {}",
+                path.display(),
+                err
+            ),
+        }
+    }
+
+    #[test]
+    #[ignore = "the responses have not been captured yet, run capture.sh and commit them"]
+    fn captured_responses() {
+        let stories: Vec<Story> = captured("hottest.json");
+        assert!(!stories.is_empty());
+        let story: Story = captured("story.json");
+        assert!(story.comments.is_some());
+        let _user: User = captured("user.json");
+        let tags: Vec<Tag> = captured("tags.json");
+        assert!(tags.iter().any(Tag::is_active));
+        let messages: Vec<Message> = captured("messages.json");
+        assert!(messages.iter().all(|message| message.body == "[redacted]"));
+    }
+
+    // The remaining fixtures are written by hand to cover the shapes of response that the
+    // models must accept, rather than captured from the site
+
+    #[test]
+    fn story_newer_format() {
+        let story: Story = fixture(include_str!(
+            "../tests/fixtures/edge_cases/story_newer_format.json"
+        ));
+        assert_eq!(story.flags, 1);
+        assert_eq!(story.upvotes, 0);
+        assert_eq!(story.submitter_user.username, "alice");
+        assert!(story.submitter_user.created_at.is_none());
+        assert!(story.user_is_author);
+
+        let comments = story.comments.unwrap();
+        let reply = &comments[1];
+        assert_eq!(reply.parent_comment, Some(CommentId("aw9xks".to_string())));
+        assert_eq!(reply.depth, Some(1));
+        assert_eq!(reply.nesting_level(), 2);
+        assert_eq!(
+            reply.comment_plain,
+            Some("Yes, it's what I ended up using.".to_string())
+        );
+        assert!(comments[0].updated_at.is_some());
+        assert!(comments[2].updated_at.is_none());
+    }
+
+    #[test]
+    fn story_2019_format() {
+        let story: Story = fixture(include_str!(
+            "../tests/fixtures/edge_cases/story_2019_format.json"
+        ));
+        assert_eq!(story.upvotes, 6);
+        assert_eq!(story.downvotes, 1);
+        assert_eq!(story.submitter_user.karma, Some(10));
+        assert!(story.submitter_user.created_at.is_some());
+
+        let comments = story.comments.unwrap();
+        let comment = &comments[0];
+        assert!(comment.updated_at.is_some());
+        assert!(comment.depth.is_none());
+        assert_eq!(comment.nesting_level(), 1);
+        assert!(comment.commenting_user.is_moderator);
+    }
+
+    #[test]
+    fn story_null_fields() {
+        let story: Story = fixture(include_str!(
+            "../tests/fixtures/edge_cases/story_null_fields.json"
+        ));
+        assert_eq!(story.title, "");
+        assert!(story.url.is_none());
+        assert_eq!(story.score, 0);
+        assert!(!story.user_is_author);
+        assert!(story.tags.is_empty());
+
+        let comments = story.comments.unwrap();
+        let comment = &comments[0];
+        assert!(comment.updated_at.is_none());
+        assert!(!comment.is_deleted);
+        assert_eq!(comment.comment, "");
+        assert_eq!(comment.nesting_level(), 1);
+    }
+
+    #[test]
+    fn story_missing_fields() {
+        let story: Story = fixture(include_str!(
+            "../tests/fixtures/edge_cases/story_missing_fields.json"
+        ));
+        assert_eq!(story.comment_count, 0);
+        assert!(story.description.is_none());
+
+        let comments = story.comments.unwrap();
+        assert!(comments[0].parent_comment.is_none());
+        assert_eq!(comments[0].nesting_level(), 1);
+    }
+
+    #[test]
+    fn user_missing_fields() {
+        let user: User = fixture(include_str!(
+            "../tests/fixtures/edge_cases/user_missing_fields.json"
+        ));
+        assert_eq!(user.username, "edge");
+        assert!(user.created_at.is_none());
+        assert!(!user.is_admin);
+        assert!(user.keybase_signatures.is_empty());
+    }
+
+    #[test]
+    fn tags_newer_and_2019_formats() {
+        let tags: Vec<Tag> = fixture(include_str!(
+            "../tests/fixtures/edge_cases/tags_newer_format.json"
+        ));
+        let active: Vec<_> = tags
+            .iter()
+            .filter(|tag| tag.is_active())
+            .map(|tag| tag.tag.as_str())
+            .collect();
+        assert_eq!(active, vec!["rust", "pdf", "announce"]);
+        assert!(tags[0].id.is_none());
+        assert_eq!(tags[3].hotness_mod, 0.0);
+
+        let tags: Vec<Tag> = fixture(include_str!(
+            "../tests/fixtures/edge_cases/tags_2019_format.json"
+        ));
+        assert!(tags[0].is_active());
+        assert!(!tags[1].is_active());
+        assert_eq!(tags[1].id.as_ref().map(|id| id.0), Some(2));
+    }
+
+    #[test]
+    fn message_null_fields() {
+        let message: Message = fixture(include_str!(
+            "../tests/fixtures/edge_cases/message_null_fields.json"
+        ));
+        assert!(message.author_username.is_none());
+        assert_eq!(message.body, "");
+        assert!(!message.has_been_read);
+    }
+
+    #[test]
+    fn missing_required_field() {
+        let json = r#"{"short_id": "abc123", "title": "No creation time"}"#;
+        assert!(serde_json::from_str::<Story>(json).is_err());
+    }
 }
@@ -0,0 +1,305 @@
+//! The comments of a story arranged by the comment they reply to
+
+use std::collections::HashMap;
+
+use super::{Comment, CommentId, Story};
+
+/// The comments of a story, arranged by the comment they reply to
+///
+/// Stories list their comments depth first, so that each comment follows its parent, with the
+/// depth given by `Comment::nesting_level`. The tree is built from that order, which means the
+/// replies to a comment always directly follow it.
+#[derive(Debug)]
+pub struct CommentTree {
+    comments: Vec<Comment>,
+    indices: HashMap<CommentId, usize>,
+    parents: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+    /// The number of comments in the subtree of each comment, including the comment itself
+    sizes: Vec<usize>,
+    collapsed: Vec<bool>,
+}
+
+/// Depth first iterator over the comments of a `CommentTree` that aren't collapsed
+pub struct Iter<'a> {
+    tree: &'a CommentTree,
+    index: usize,
+}
+
+impl CommentTree {
+    /// Build a tree from comments listed depth first, as in `Story::comments`
+    pub fn new(comments: Vec<Comment>) -> Self {
+        let mut parents = Vec::with_capacity(comments.len());
+        let mut children = vec![Vec::new(); comments.len()];
+        let mut indices = HashMap::with_capacity(comments.len());
+
+        // The comments that the next comment could be replying to, innermost last
+        let mut ancestors: Vec<(usize, u32)> = Vec::new();
+        for (index, comment) in comments.iter().enumerate() {
+            while ancestors
+                .last()
+                .is_some_and(|&(_, indent)| indent >= comment.nesting_level())
+            {
+                ancestors.pop();
+            }
+
+            let parent = ancestors.last().map(|&(parent, _)| parent);
+            if let Some(parent) = parent {
+                children[parent].push(index);
+            }
+            parents.push(parent);
+            ancestors.push((index, comment.nesting_level()));
+            indices.insert(comment.short_id.clone(), index);
+        }
+
+        // Replies always come after the comment they reply to, so sizes can be summed up from
+        // the end
+        let mut sizes = vec![1; comments.len()];
+        for index in (0..comments.len()).rev() {
+            if let Some(parent) = parents[index] {
+                sizes[parent] += sizes[index];
+            }
+        }
+
+        CommentTree {
+            collapsed: vec![false; comments.len()],
+            comments,
+            indices,
+            parents,
+            children,
+            sizes,
+        }
+    }
+
+    /// The number of comments in the tree, including collapsed ones
+    pub fn len(&self) -> usize {
+        self.comments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.comments.is_empty()
+    }
+
+    pub fn get(&self, comment_id: &CommentId) -> Option<&Comment> {
+        self.index(comment_id).map(|index| &self.comments[index])
+    }
+
+    /// The comments that reply directly to the story
+    pub fn roots(&self) -> Vec<&Comment> {
+        self.parents
+            .iter()
+            .enumerate()
+            .filter(|(_, parent)| parent.is_none())
+            .map(|(index, _)| &self.comments[index])
+            .collect()
+    }
+
+    /// The comment that `comment_id` replies to, `None` for replies to the story
+    pub fn parent(&self, comment_id: &CommentId) -> Option<&Comment> {
+        self.index(comment_id)
+            .and_then(|index| self.parents[index])
+            .map(|parent| &self.comments[parent])
+    }
+
+    /// The comments that reply directly to `comment_id`
+    pub fn children(&self, comment_id: &CommentId) -> Vec<&Comment> {
+        self.index(comment_id)
+            .map(|index| {
+                self.children[index]
+                    .iter()
+                    .map(|&child| &self.comments[child])
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The number of comments in the subtree of `comment_id`, including itself
+    ///
+    /// Returns 0 if the comment isn't in the tree.
+    pub fn subtree_size(&self, comment_id: &CommentId) -> usize {
+        self.index(comment_id)
+            .map(|index| self.sizes[index])
+            .unwrap_or(0)
+    }
+
+    /// Iterate over the comments depth first, skipping the replies to collapsed comments
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            tree: self,
+            index: 0,
+        }
+    }
+
+    pub fn is_collapsed(&self, comment_id: &CommentId) -> bool {
+        self.index(comment_id)
+            .is_some_and(|index| self.collapsed[index])
+    }
+
+    /// Hide or show the replies to `comment_id` when iterating
+    pub fn set_collapsed(&mut self, comment_id: &CommentId, collapsed: bool) {
+        if let Some(index) = self.index(comment_id) {
+            self.collapsed[index] = collapsed;
+        }
+    }
+
+    /// Collapse the subtrees of comments that have been deleted or removed by a moderator
+    pub fn collapse_removed(&mut self) {
+        for (index, comment) in self.comments.iter().enumerate() {
+            if comment.is_deleted || comment.is_moderated {
+                self.collapsed[index] = true;
+            }
+        }
+    }
+
+    /// Take the comments back out of the tree, in their original order
+    pub fn into_comments(self) -> Vec<Comment> {
+        self.comments
+    }
+
+    fn index(&self, comment_id: &CommentId) -> Option<usize> {
+        self.indices.get(comment_id).cloned()
+    }
+}
+
+impl From<Story> for CommentTree {
+    fn from(story: Story) -> Self {
+        CommentTree::new(story.comments.unwrap_or_default())
+    }
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Comment;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tree = self.tree;
+        let comment = tree.comments.get(self.index)?;
+
+        // The replies to a comment directly follow it, so skipping them skips its subtree
+        self.index += if tree.collapsed[self.index] {
+            tree.sizes[self.index]
+        } else {
+            1
+        };
+
+        Some(comment)
+    }
+}
+
+impl<'a> IntoIterator for &'a CommentTree {
+    type Item = &'a Comment;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment(short_id: &str, indent_level: u32, is_deleted: bool) -> Comment {
+        let json = format!(
+            r#"{{
+                "short_id": "{short_id}",
+                "short_id_url": "https://lobste.rs/c/{short_id}",
+                "created_at": "2019-04-21T11:07:44.000-05:00",
+                "updated_at": "2019-04-21T11:07:44.000-05:00",
+                "is_deleted": {is_deleted},
+                "is_moderated": false,
+                "score": 1,
+                "upvotes": 1,
+                "downvotes": 0,
+                "comment": "<p>Hello</p>",
+                "url": "https://lobste.rs/s/abc123/a_story#c_{short_id}",
+                "indent_level": {indent_level},
+                "commenting_user": {{
+                    "username": "alice",
+                    "created_at": "2018-01-01T00:00:00.000-05:00",
+                    "is_admin": false,
+                    "is_moderator": false,
+                    "avatar_url": "/avatars/alice-100.png"
+                }}
+            }}"#,
+            short_id = short_id,
+            indent_level = indent_level,
+            is_deleted = is_deleted,
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    fn id(short_id: &str) -> CommentId {
+        CommentId(short_id.to_string())
+    }
+
+    fn short_ids<'a, I: IntoIterator<Item = &'a Comment>>(comments: I) -> Vec<&'a str> {
+        comments
+            .into_iter()
+            .map(|comment| comment.short_id.0.as_str())
+            .collect()
+    }
+
+    /// a
+    /// ├ b
+    /// │ └ c
+    /// └ d (deleted)
+    ///   └ e
+    /// f
+    fn tree() -> CommentTree {
+        CommentTree::new(vec![
+            comment("a", 1, false),
+            comment("b", 2, false),
+            comment("c", 3, false),
+            comment("d", 2, true),
+            comment("e", 3, false),
+            comment("f", 1, false),
+        ])
+    }
+
+    #[test]
+    fn parents_and_children() {
+        let tree = tree();
+        assert_eq!(short_ids(tree.roots()), vec!["a", "f"]);
+        assert_eq!(short_ids(tree.children(&id("a"))), vec!["b", "d"]);
+        assert!(tree.children(&id("f")).is_empty());
+        assert_eq!(
+            tree.parent(&id("e")).map(|c| c.short_id.0.as_str()),
+            Some("d")
+        );
+        assert!(tree.parent(&id("a")).is_none());
+        assert!(tree.get(&id("z")).is_none());
+    }
+
+    #[test]
+    fn subtree_sizes() {
+        let tree = tree();
+        assert_eq!(tree.subtree_size(&id("a")), 5);
+        assert_eq!(tree.subtree_size(&id("b")), 2);
+        assert_eq!(tree.subtree_size(&id("f")), 1);
+        assert_eq!(tree.subtree_size(&id("z")), 0);
+    }
+
+    #[test]
+    fn collapse_removed() {
+        let mut tree = tree();
+        assert_eq!(short_ids(&tree), vec!["a", "b", "c", "d", "e", "f"]);
+
+        tree.collapse_removed();
+        assert!(tree.is_collapsed(&id("d")));
+        assert_eq!(short_ids(&tree), vec!["a", "b", "c", "d", "f"]);
+
+        tree.set_collapsed(&id("a"), true);
+        assert_eq!(short_ids(&tree), vec!["a", "f"]);
+    }
+
+    #[test]
+    fn indent_jumps() {
+        // A reply whose parent is missing from the listing is attached to the nearest comment
+        // above it that is less indented
+        let tree = CommentTree::new(vec![comment("a", 1, false), comment("b", 3, false)]);
+        assert_eq!(
+            tree.parent(&id("b")).map(|c| c.short_id.0.as_str()),
+            Some("a")
+        );
+    }
+}
@@ -0,0 +1,258 @@
+//! Timestamps in the RFC 3339 format sent by the site
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+
+/// A point in time, kept as the RFC 3339 string sent by the site
+///
+/// The string is checked when a timestamp is deserialized, so a malformed timestamp is a
+/// deserialization error. Timestamps compare by the point in time they represent, regardless of
+/// their UTC offset. With the `chrono` feature enabled they can be converted into a
+/// `chrono::DateTime` with `to_datetime`.
+#[derive(Debug, Clone)]
+pub struct Timestamp {
+    rfc3339: String,
+    /// Seconds since the Unix epoch, and nanoseconds past that second
+    instant: (i64, u32),
+}
+
+impl Timestamp {
+    /// Parse a timestamp like `2019-04-21T11:07:44.000-05:00`
+    ///
+    /// Returns `None` if `rfc3339` is not a valid RFC 3339 timestamp.
+    pub fn parse(rfc3339: &str) -> Option<Self> {
+        instant(rfc3339).map(|instant| Timestamp {
+            rfc3339: rfc3339.to_string(),
+            instant,
+        })
+    }
+
+    /// The timestamp as sent by the site
+    pub fn as_str(&self) -> &str {
+        &self.rfc3339
+    }
+
+    /// Convert the timestamp into a `chrono::DateTime` with the UTC offset sent by the site
+    #[cfg(feature = "chrono")]
+    pub fn to_datetime(&self) -> chrono::DateTime<chrono::FixedOffset> {
+        // NOTE(expect): Safe because `instant` only accepts timestamps that chrono can parse
+        chrono::DateTime::parse_from_rfc3339(&self.rfc3339).expect("invalid timestamp")
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.rfc3339)
+    }
+}
+
+impl PartialEq for Timestamp {
+    fn eq(&self, other: &Self) -> bool {
+        self.instant == other.instant
+    }
+}
+
+impl Eq for Timestamp {}
+
+impl Hash for Timestamp {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.instant.hash(state);
+    }
+}
+
+impl PartialOrd for Timestamp {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Timestamp {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.instant.cmp(&other.instant)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let rfc3339 = String::deserialize(deserializer)?;
+        Timestamp::parse(&rfc3339)
+            .ok_or_else(|| de::Error::custom(format!("invalid timestamp: {}", rfc3339)))
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.rfc3339)
+    }
+}
+
+/// The seconds since the Unix epoch and nanoseconds of an RFC 3339 timestamp
+///
+/// Only the `YYYY-MM-DDTHH:MM:SS[.fraction](Z|+HH:MM|-HH:MM)` form is accepted, which is what
+/// the site sends and a subset of what chrono accepts.
+fn instant(rfc3339: &str) -> Option<(i64, u32)> {
+    let bytes = rfc3339.as_bytes();
+    let number = |start: usize, len: usize| -> Option<i64> {
+        let digits = bytes.get(start..start + len)?;
+        if digits.iter().all(u8::is_ascii_digit) {
+            Some(digits.iter().fold(0, |n, d| n * 10 + i64::from(d - b'0')))
+        } else {
+            None
+        }
+    };
+    let separators = [(4, b'-'), (7, b'-'), (10, b'T'), (13, b':'), (16, b':')];
+    if separators
+        .iter()
+        .any(|&(index, separator)| bytes.get(index) != Some(&separator))
+    {
+        return None;
+    }
+
+    let (year, month, day) = (number(0, 4)?, number(5, 2)?, number(8, 2)?);
+    let (hour, minute, second) = (number(11, 2)?, number(14, 2)?, number(17, 2)?);
+    if !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+
+    let mut rest = &bytes[19..];
+    let mut nanos = 0;
+    if rest.first() == Some(&b'.') {
+        let digits = rest[1..].iter().take_while(|d| d.is_ascii_digit()).count();
+        if digits == 0 {
+            return None;
+        }
+        // Digits past nanoseconds are ignored, as chrono does
+        for (place, digit) in rest[1..=digits].iter().take(9).enumerate() {
+            nanos += u32::from(digit - b'0') * 10u32.pow(8 - place as u32);
+        }
+        rest = &rest[digits + 1..];
+    }
+
+    let offset = match rest {
+        [b'Z'] => 0,
+        [sign, _, _, b':', _, _] if *sign == b'+' || *sign == b'-' => {
+            let (hours, minutes) = (number(bytes.len() - 5, 2)?, number(bytes.len() - 2, 2)?);
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            let offset = hours * 3600 + minutes * 60;
+            if *sign == b'-' {
+                -offset
+            } else {
+                offset
+            }
+        }
+        _ => return None,
+    };
+
+    let seconds = days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second;
+    Some((seconds - offset, nanos))
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The number of days from 1970-01-01 to a date in the proleptic Gregorian calendar
+///
+/// This is the `days_from_civil` algorithm described by Howard Hinnant.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_valid() {
+        let timestamp = Timestamp::parse("2019-04-21T11:07:44.000-05:00").unwrap();
+        assert_eq!(timestamp.as_str(), "2019-04-21T11:07:44.000-05:00");
+        assert_eq!(timestamp.instant, (1_555_862_864, 0));
+
+        assert_eq!(
+            Timestamp::parse("1970-01-01T00:00:00Z").unwrap().instant,
+            (0, 0)
+        );
+        assert_eq!(
+            Timestamp::parse("2024-02-29T23:59:59.25+10:00")
+                .unwrap()
+                .instant,
+            (1_709_215_199, 250_000_000)
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        let invalid = [
+            "",
+            "yesterday",
+            "2019-04-21",
+            "2019-04-21 11:07:44 -0500",
+            "2019-04-21T11:07:44",
+            "2019-13-01T00:00:00Z",
+            "2019-02-29T00:00:00Z",
+            "2019-04-21T24:00:00Z",
+            "2019-04-21T11:07:44.Z",
+            "2019-04-21T11:07:44+0500",
+            "2019-04-21T11:07:44+24:00",
+        ];
+        for rfc3339 in &invalid {
+            assert!(Timestamp::parse(rfc3339).is_none(), "{} parsed", rfc3339);
+        }
+    }
+
+    #[test]
+    fn compare_across_offsets() {
+        let chicago = Timestamp::parse("2019-04-21T11:07:44.000-05:00").unwrap();
+        let utc = Timestamp::parse("2019-04-21T16:07:44Z").unwrap();
+        let later = Timestamp::parse("2019-04-21T12:00:00+00:00").unwrap();
+        assert_eq!(chicago, utc);
+        assert!(later < chicago);
+    }
+
+    #[test]
+    fn deserialize_invalid() {
+        assert!(serde_json::from_str::<Timestamp>(r#""yesterday""#).is_err());
+        let timestamp: Timestamp = serde_json::from_str(r#""2019-04-21T16:07:44Z""#).unwrap();
+        assert_eq!(
+            serde_json::to_string(&timestamp).unwrap(),
+            r#""2019-04-21T16:07:44Z""#
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn to_datetime() {
+        let timestamp = Timestamp::parse("2019-04-21T11:07:44.000-05:00").unwrap();
+        let datetime = timestamp.to_datetime();
+        assert_eq!(datetime.timestamp(), 1_555_862_864);
+        assert_eq!(datetime.offset().local_minus_utc(), -5 * 3600);
+    }
+}
@@ -0,0 +1,30 @@
+#!/bin/sh
+# Save responses from the site as fixtures for the model tests in src/models.rs
+#
+# Run from this directory. /messages.json needs a logged in session, set LOBSTERS_COOKIE to
+# the value of the Cookie header of one. The bodies of the messages are redacted, check the
+# subjects and usernames before committing them too.
+set -e
+
+if [ -z "${LOBSTERS_COOKIE:-}" ]; then
+    echo "LOBSTERS_COOKIE is not set, messages.json needs a logged in session" >&2
+    exit 1
+fi
+
+base=${LOBSTERS_URL:-https://lobste.rs}
+fetch() {
+    curl -sSf -H "Cookie: ${LOBSTERS_COOKIE:-}" "$base/$1" -o "$2"
+}
+
+fetch hottest.json hottest.json
+fetch tags.json tags.json
+
+# The story and user are the first story on the front page and its submitter
+story=$(grep -o '"short_id":"[^"]*"' hottest.json | head -n 1 | cut -d '"' -f 4)
+fetch "s/$story.json" story.json
+user=$(grep -Eo '"(submitter_user|username)":"[^"]*"' story.json | head -n 1 | cut -d '"' -f 4)
+fetch "u/$user.json" user.json
+
+fetch messages.json messages.raw.json
+sed -E 's/"body":"([^"\\]|\\.)*"/"body":"[redacted]"/g' messages.raw.json > messages.json
+rm messages.raw.json